@@ -3,6 +3,7 @@ use std::default::Default;
 use css_color_parser::Color as CssColor;
 use stringstruct::StringStruct;
 
+use crate::custom::STITCH_PREFIX;
 use crate::errors::*;
 use crate::header::Header;
 
@@ -17,7 +18,7 @@ use crate::header::Header;
 //   emptymarker = marker used for empty cells
 //       Ideally, we won't draw the empty cells.
 
-fn parse_char_name(s: &str) -> std::result::Result<char, String> {
+pub(crate) fn parse_char_name(s: &str) -> std::result::Result<char, String> {
     if s.is_empty() {
         // TODO: Get the line number in here.
         return Err("Value for char cannot be empty string.".into());
@@ -37,16 +38,94 @@ fn parse_char_name(s: &str) -> std::result::Result<char, String> {
     Ok(s.chars().next().unwrap())
 }
 
+// Rendering time and memory grow with the square of the quality.
+const MAX_QUALITY: u32 = 8;
+
+fn parse_quality(s: &str) -> std::result::Result<u32, String> {
+    match s.trim().parse::<u32>() {
+        Ok(n) if (1..=MAX_QUALITY).contains(&n) => Ok(n),
+        _ => Err(format!(
+            "'{}' is not a quality from 1 to {}.",
+            s, MAX_QUALITY
+        )),
+    }
+}
+
+// The char of a stitch that has given way to another stitch's char.
+pub(crate) const UNBOUND_CHAR: char = '\0';
+
+// A palette is a whitespace-separated list of char:color pairs, e.g. "A:white B:#1e3f66".
+fn parse_palette(s: &str) -> std::result::Result<Vec<(char, CssColor)>, String> {
+    s.split_whitespace()
+        .map(|entry| {
+            let mut splits = entry.splitn(2, ':');
+            let ch = parse_char_name(splits.next().unwrap_or_default())?;
+            let color = splits
+                .next()
+                .ok_or_else(|| {
+                    format!("Palette entry '{}' should have the form char:color.", entry)
+                })?
+                .parse::<CssColor>()
+                .map_err(|_| format!("'{}' does not contain a valid color.", entry))?;
+            Ok((ch, color))
+        })
+        .collect()
+}
+
+// How the cells of a chart are read.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Mode {
+    // Each cell is the stitch worked there.
+    #[default]
+    Standard,
+    // Each cell is the palette color that shows there. See mosaic.rs.
+    Mosaic,
+    // Each cell is the palette color that shows on the front. See double_knitting.rs.
+    DoubleKnitting,
+    // Each charted row is one pass, with two passes, dark then light, to each row.
+    Brioche,
+}
+
+fn parse_mode(s: &str) -> std::result::Result<Mode, String> {
+    match s.to_ascii_lowercase().as_str() {
+        "standard" => Ok(Mode::Standard),
+        "mosaic" => Ok(Mode::Mosaic),
+        "double_knitting" => Ok(Mode::DoubleKnitting),
+        "brioche" => Ok(Mode::Brioche),
+        _ => Err(format!("'{}' is not a chart mode.", s)),
+    }
+}
+
+fn parse_positive(s: &str) -> std::result::Result<f64, String> {
+    match s.trim().parse::<f64>() {
+        Ok(n) if n > 0.0 => Ok(n),
+        _ => Err(format!("'{}' is not a positive number.", s)),
+    }
+}
+
 #[derive(Debug, StringStruct)]
 pub struct Attributes {
     pub rows: usize,
     pub columns: usize,
 
-    #[ssfield(default = "15")]
+    // In pixels.
+    #[ssfield(default = "15", parse = "parse_positive")]
     pub cell_size: f64,
     #[ssfield(default = "10")]
     pub dot_size: f64,
 
+    // Given a dpi and a physical cell size, the cell size in pixels is worked out from them
+    // instead. Zero means not given. A physical cell size without a dpi is ignored, with a
+    // warning.
+    pub dpi: f64,
+    pub cell_mm: f64,
+    pub cell_inches: f64,
+
+    // Each pixel of a stitch symbol is rendered from quality x quality samples, which smooths
+    // the edges of lines and dots. At most MAX_QUALITY.
+    #[ssfield(default = "1", parse = "parse_quality")]
+    pub quality: u32,
+
     #[ssfield(default = ".", parse = "parse_char_name")]
     pub knit: char,
     #[ssfield(default = "X", parse = "parse_char_name")]
@@ -54,6 +133,55 @@ pub struct Attributes {
     #[ssfield(default = "SPACE", parse = "parse_char_name")]
     pub empty: char,
 
+    #[ssfield(default = "O", parse = "parse_char_name")]
+    pub yo: char,
+    #[ssfield(default = "(", parse = "parse_char_name")]
+    pub m1l: char,
+    #[ssfield(default = ")", parse = "parse_char_name")]
+    pub m1r: char,
+    #[ssfield(default = "/", parse = "parse_char_name")]
+    pub k2tog: char,
+    #[ssfield(default = "\\", parse = "parse_char_name")]
+    pub ssk: char,
+    #[ssfield(default = ",", parse = "parse_char_name")]
+    pub p2tog: char,
+    #[ssfield(default = ">", parse = "parse_char_name")]
+    pub k3tog: char,
+    #[ssfield(default = "<", parse = "parse_char_name")]
+    pub sssk: char,
+    #[ssfield(default = "^", parse = "parse_char_name")]
+    pub cdd: char,
+
+    #[ssfield(default = "&", parse = "parse_char_name")]
+    pub ktbl: char,
+    #[ssfield(default = "%", parse = "parse_char_name")]
+    pub ptbl: char,
+    #[ssfield(default = "-", parse = "parse_char_name")]
+    pub sl_wyib: char,
+    #[ssfield(default = "_", parse = "parse_char_name")]
+    pub sl_wyif: char,
+    #[ssfield(default = "@", parse = "parse_char_name")]
+    pub bobble: char,
+    #[ssfield(default = "$", parse = "parse_char_name")]
+    pub nupp: char,
+    #[ssfield(default = "!", parse = "parse_char_name")]
+    pub wrap_turn: char,
+
+    #[ssfield(default = "+", parse = "parse_char_name")]
+    pub brk: char,
+    #[ssfield(default = "~", parse = "parse_char_name")]
+    pub brp: char,
+    #[ssfield(default = ":", parse = "parse_char_name")]
+    pub sl1yo: char,
+    #[ssfield(default = "]", parse = "parse_char_name")]
+    pub brk2tog: char,
+    #[ssfield(default = "[", parse = "parse_char_name")]
+    pub brssk: char,
+    #[ssfield(default = "}", parse = "parse_char_name")]
+    pub brk3tog: char,
+    #[ssfield(default = ";", parse = "parse_char_name")]
+    pub brkyobrk: char,
+
     // TODO: implement this.
     #[ssfield(default = "whitesmoke")]
     pub background_color: CssColor,
@@ -61,7 +189,61 @@ pub struct Attributes {
     #[ssfield(default = "darkslategray")]
     pub grid_color: CssColor,
 
+    #[ssfield(default = "magenta")]
+    pub highlight_color: CssColor,
+
+    // Heavier grid lines every so many rows and columns, counted from the bottom right.
+    // Zero means no major lines.
+    pub major_row_interval: usize,
+    pub major_column_interval: usize,
+    #[ssfield(default = "black")]
+    pub major_row_color: CssColor,
+    #[ssfield(default = "black")]
+    pub major_column_color: CssColor,
+    #[ssfield(default = "2")]
+    pub major_row_width: f64,
+    #[ssfield(default = "2")]
+    pub major_column_width: f64,
+
+    #[ssfield(parse = "parse_palette")]
+    pub palette: Vec<(char, CssColor)>,
+
     pub in_the_round: bool,
+
+    #[ssfield(default = "standard", parse = "parse_mode")]
+    pub mode: Mode,
+
+    // Gauge. Zero means unknown.
+    pub stitches_per_10cm: f64,
+    pub rows_per_10cm: f64,
+
+    // For yarn estimates. Zero means unknown. The swatch is 10cm x 10cm, at the gauge above.
+    pub yards_per_stitch: f64,
+    pub swatch_grams: f64,
+
+    // The longest float allowed in colorwork. Zero means floats aren't checked.
+    pub max_float: usize,
+
+    // Render a preview of the fabric, rather than a chart.
+    pub true_proportions: bool,
+
+    // Number each intarsia bobbin in the cell where it is first used.
+    pub bobbin_labels: bool,
+
+    // Machine knitting images and punchcards. The palette and stitch chars knit in the contrast
+    // yarn. Empty means everything but knits, empty cells and the first palette color.
+    pub machine_contrast: String,
+    // Main yarn is black and contrast white, instead of the other way round.
+    pub machine_invert: bool,
+    // Reflect the image left to right.
+    pub machine_mirror: bool,
+
+    // Drawn above and below the chart, and saved in the image metadata.
+    pub title: String,
+    pub designer: String,
+    pub copyright: String,
+    pub notes: String,
+    pub size: String,
 }
 
 impl Attributes {
@@ -69,12 +251,79 @@ impl Attributes {
         let mut attrs = Attributes::default();
 
         for (name, line) in hdr.iter() {
-            // TODO: return a Result from set_value.
-            attrs.set_value(name, &line.value);
+            attrs
+                .set_value(name, &line.value)
+                .map_err(|reason| ErrorKind::BadAttrValue(name.clone(), reason))?;
+        }
+
+        // Mosaic and double knitting are worked in exactly two colors.
+        let reason = match attrs.mode {
+            Mode::Mosaic => "Mosaic charts need a palette of two colors.",
+            Mode::DoubleKnitting => "Double knitting charts need a palette of two colors.",
+            Mode::Standard | Mode::Brioche => "",
+        };
+        if !reason.is_empty() && attrs.palette.len() != 2 {
+            return Err(ErrorKind::BadAttrValue("palette".into(), reason.into()).into());
         }
 
         return Ok(attrs);
     }
+
+    // Whether the name is one a header can set: an attribute or a custom stitch.
+    pub(crate) fn is_name(name: &str) -> bool {
+        name.starts_with(STITCH_PREFIX) || Attributes::is_value_name(name)
+    }
+
+    // The stitch chars, with the names they are set by.
+    fn stitch_chars(&mut self) -> [(&'static str, &mut char); 26] {
+        [
+            ("knit", &mut self.knit),
+            ("purl", &mut self.purl),
+            ("empty", &mut self.empty),
+            ("yo", &mut self.yo),
+            ("m1l", &mut self.m1l),
+            ("m1r", &mut self.m1r),
+            ("k2tog", &mut self.k2tog),
+            ("ssk", &mut self.ssk),
+            ("p2tog", &mut self.p2tog),
+            ("k3tog", &mut self.k3tog),
+            ("sssk", &mut self.sssk),
+            ("cdd", &mut self.cdd),
+            ("ktbl", &mut self.ktbl),
+            ("ptbl", &mut self.ptbl),
+            ("sl_wyib", &mut self.sl_wyib),
+            ("sl_wyif", &mut self.sl_wyif),
+            ("bobble", &mut self.bobble),
+            ("nupp", &mut self.nupp),
+            ("wrap_turn", &mut self.wrap_turn),
+            ("brk", &mut self.brk),
+            ("brp", &mut self.brp),
+            ("sl1yo", &mut self.sl1yo),
+            ("brk2tog", &mut self.brk2tog),
+            ("brssk", &mut self.brssk),
+            ("brk3tog", &mut self.brk3tog),
+            ("brkyobrk", &mut self.brkyobrk),
+        ]
+    }
+
+    // Stitch chars the header doesn't set give way to the chars it does set, and to the
+    // palette and custom stitch chars, so that e.g. purl=- doesn't clash with the default
+    // sl_wyib char. A stitch that gives way has no char, and can't be used in the chart.
+    pub(crate) fn unbind_default_chars(&mut self, hdr: &Header, custom: &[char]) {
+        let set: Vec<String> = hdr.iter().map(|(name, _)| name.clone()).collect();
+        let mut explicit: Vec<char> = self.palette.iter().map(|(ch, _)| *ch).collect();
+        explicit.extend(custom);
+        for (name, ch) in self.stitch_chars().iter() {
+            if set.iter().any(|s| s == name) {
+                explicit.push(**ch);
+            }
+        }
+        for (name, ch) in self.stitch_chars().iter_mut() {
+            if !set.iter().any(|s| s == name) && explicit.contains(ch) {
+                **ch = UNBOUND_CHAR;
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -92,9 +341,20 @@ mod test {
         assert_eq!(0, attrs.columns);
         assert_eq!(15.0, attrs.cell_size);
         assert_eq!(10.0, attrs.dot_size);
+        assert_eq!(0.0, attrs.dpi);
+        assert_eq!(0.0, attrs.cell_mm);
+        assert_eq!(1, attrs.quality);
         assert_eq!('.', attrs.knit);
         assert_eq!('X', attrs.purl);
         assert_eq!(' ', attrs.empty);
+        assert_eq!('O', attrs.yo);
+        assert_eq!('/', attrs.k2tog);
+        assert_eq!('\\', attrs.ssk);
+        assert_eq!('&', attrs.ktbl);
+        assert_eq!('-', attrs.sl_wyib);
+        assert_eq!('!', attrs.wrap_turn);
+        assert_eq!('+', attrs.brk);
+        assert_eq!(':', attrs.sl1yo);
         assert_eq!(
             CssColor::from_str("whitesmoke").unwrap(),
             attrs.background_color
@@ -104,6 +364,24 @@ mod test {
             attrs.grid_color
         );
         assert_eq!(false, attrs.in_the_round);
+        assert_eq!(Mode::Standard, attrs.mode);
+        assert_eq!(
+            CssColor::from_str("magenta").unwrap(),
+            attrs.highlight_color
+        );
+        assert_eq!(0, attrs.major_row_interval);
+        assert_eq!(0, attrs.major_column_interval);
+        assert_eq!(CssColor::from_str("black").unwrap(), attrs.major_row_color);
+        assert_eq!(2.0, attrs.major_column_width);
+        assert!(attrs.palette.is_empty());
+        assert_eq!(0.0, attrs.stitches_per_10cm);
+        assert_eq!(0.0, attrs.rows_per_10cm);
+        assert_eq!(0.0, attrs.yards_per_stitch);
+        assert_eq!(0.0, attrs.swatch_grams);
+        assert_eq!(0, attrs.max_float);
+        assert_eq!(false, attrs.true_proportions);
+        assert_eq!("", attrs.title);
+        assert_eq!("", attrs.notes);
     }
 
     #[test]
@@ -117,6 +395,37 @@ mod test {
         assert!(parse_char_name("XX").is_err());
     }
 
+    #[test]
+    fn test_parse_palette() {
+        let palette = parse_palette("A:white  B:#000080\tSPACE:red").unwrap();
+
+        assert_eq!(3, palette.len());
+        assert_eq!(('A', CssColor::from_str("white").unwrap()), palette[0]);
+        assert_eq!(('B', CssColor::from_str("#000080").unwrap()), palette[1]);
+        assert_eq!((' ', CssColor::from_str("red").unwrap()), palette[2]);
+
+        assert!(parse_palette("").unwrap().is_empty());
+        assert!(parse_palette("A").is_err());
+        assert!(parse_palette("A:notacolor").is_err());
+        assert!(parse_palette("AB:white").is_err());
+    }
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!(Mode::Standard, parse_mode("standard").unwrap());
+        assert_eq!(Mode::Mosaic, parse_mode("Mosaic").unwrap());
+        assert_eq!(Mode::DoubleKnitting, parse_mode("double_knitting").unwrap());
+        assert!(parse_mode("intarsia").is_err());
+    }
+
+    #[test]
+    fn test_parse_quality() {
+        assert_eq!(4, parse_quality("4").unwrap());
+        assert!(parse_quality("0").is_err());
+        assert!(parse_quality("9").is_err());
+        assert!(parse_quality("4294967295").is_err());
+    }
+
     #[test]
     fn test_attributes() {
         let header_str = r#"
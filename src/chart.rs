@@ -1,28 +1,230 @@
 use std::fmt::{self, Debug};
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Write};
 use std::iter::{repeat, repeat_with};
 
 use css_color_parser::Color as CssColor;
 
-use crate::attributes::Attributes;
+use crate::attributes::{Attributes, Mode, UNBOUND_CHAR};
+use crate::custom::{custom_stitches, CustomStitch};
 use crate::errors::*;
 use crate::header::Header;
 
+const MM_PER_INCH: f64 = 25.4;
+
 #[derive(Debug)]
 pub struct Chart {
+    header: Header,
     attributes: Attributes,
+    custom: Vec<CustomStitch>,
     stitches: Vec<Vec<Stitch>>,
+    warnings: Vec<String>,
 
     rows: usize,
     cols: usize,
 }
 
-#[derive(Clone, Copy)]
+// Heavier grid lines, drawn every `interval` rows or columns.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MajorLines {
+    pub interval: usize,
+    pub color: CssColor,
+    pub width: f64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum Stitch {
     Knit,
     Purl,
     Empty,
+    // A colorwork stitch, knit in the palette color with this index.
+    Color(usize),
+
+    // Increases
+    Yo,
+    M1L,
+    M1R,
+
+    // Decreases
+    K2tog,
+    Ssk,
+    P2tog,
+    K3tog,
+    Sssk,
+    Cdd,
+
+    // Twisted and slipped stitches
+    Ktbl,
+    Ptbl,
+    SlWyib,
+    SlWyif,
+
+    // Textures
+    Bobble,
+    Nupp,
+
+    // Short rows
+    WrapTurn,
+
+    // Brioche
+    Brk,
+    Brp,
+    Sl1yo,
+    Brk2tog,
+    Brssk,
+    Brk3tog,
+    BrkYoBrk,
+
+    // A stitch defined in the header, with this index in Chart::custom_stitches().
+    Custom(usize),
+}
+
+// The stitches, other than knit, purl, empty, colors and custom stitches, that are bound to
+// chars in the header.
+const BOUND_STITCHES: [Stitch; 23] = [
+    Stitch::Yo,
+    Stitch::M1L,
+    Stitch::M1R,
+    Stitch::K2tog,
+    Stitch::Ssk,
+    Stitch::P2tog,
+    Stitch::K3tog,
+    Stitch::Sssk,
+    Stitch::Cdd,
+    Stitch::Ktbl,
+    Stitch::Ptbl,
+    Stitch::SlWyib,
+    Stitch::SlWyif,
+    Stitch::Bobble,
+    Stitch::Nupp,
+    Stitch::WrapTurn,
+    Stitch::Brk,
+    Stitch::Brp,
+    Stitch::Sl1yo,
+    Stitch::Brk2tog,
+    Stitch::Brssk,
+    Stitch::Brk3tog,
+    Stitch::BrkYoBrk,
+];
+
+impl Stitch {
+    pub fn name(self) -> &'static str {
+        match self {
+            Stitch::Knit => "knit",
+            Stitch::Purl => "purl",
+            Stitch::Empty => "no stitch",
+            Stitch::Color(_) => "color",
+            Stitch::Yo => "yarn over",
+            Stitch::M1L => "make one left",
+            Stitch::M1R => "make one right",
+            Stitch::K2tog => "knit two together",
+            Stitch::Ssk => "slip, slip, knit",
+            Stitch::P2tog => "purl two together",
+            Stitch::K3tog => "knit three together",
+            Stitch::Sssk => "slip, slip, slip, knit",
+            Stitch::Cdd => "central double decrease",
+            Stitch::Ktbl => "knit through the back loop",
+            Stitch::Ptbl => "purl through the back loop",
+            Stitch::SlWyib => "slip with yarn in back",
+            Stitch::SlWyif => "slip with yarn in front",
+            Stitch::Bobble => "bobble",
+            Stitch::Nupp => "nupp",
+            Stitch::WrapTurn => "wrap and turn",
+            Stitch::Brk => "brioche knit",
+            Stitch::Brp => "brioche purl",
+            Stitch::Sl1yo => "slip one, yarn over",
+            Stitch::Brk2tog => "brioche knit two together",
+            Stitch::Brssk => "brioche slip, slip, knit",
+            Stitch::Brk3tog => "brioche knit three together",
+            Stitch::BrkYoBrk => "brioche knit, yarn over, brioche knit",
+            Stitch::Custom(_) => "custom stitch",
+        }
+    }
+
+    // How the stitch is written in row-by-row instructions. Use Chart::abbreviation() for
+    // custom stitches.
+    pub fn abbreviation(self) -> &'static str {
+        match self {
+            Stitch::Knit | Stitch::Color(_) => "k",
+            Stitch::Purl => "p",
+            Stitch::Empty => "",
+            Stitch::Yo => "yo",
+            Stitch::M1L => "m1l",
+            Stitch::M1R => "m1r",
+            Stitch::K2tog => "k2tog",
+            Stitch::Ssk => "ssk",
+            Stitch::P2tog => "p2tog",
+            Stitch::K3tog => "k3tog",
+            Stitch::Sssk => "sssk",
+            Stitch::Cdd => "cdd",
+            Stitch::Ktbl => "k1tbl",
+            Stitch::Ptbl => "p1tbl",
+            Stitch::SlWyib => "sl1 wyib",
+            Stitch::SlWyif => "sl1 wyif",
+            Stitch::Bobble => "mb",
+            Stitch::Nupp => "nupp",
+            Stitch::WrapTurn => "w&t",
+            Stitch::Brk => "brk",
+            Stitch::Brp => "brp",
+            Stitch::Sl1yo => "sl1yo",
+            Stitch::Brk2tog => "brk2tog",
+            Stitch::Brssk => "brssk",
+            Stitch::Brk3tog => "brk3tog",
+            Stitch::BrkYoBrk => "brkyobrk",
+            Stitch::Custom(_) => "custom",
+        }
+    }
+
+    // The number of stitches this takes off the left needle. In brioche, a stitch and the yarn
+    // over it was slipped with count as one.
+    pub fn consumes(self) -> usize {
+        match self {
+            Stitch::Empty | Stitch::Yo | Stitch::M1L | Stitch::M1R => 0,
+            Stitch::K2tog | Stitch::Ssk | Stitch::P2tog | Stitch::Brk2tog | Stitch::Brssk => 2,
+            Stitch::K3tog | Stitch::Sssk | Stitch::Cdd | Stitch::Brk3tog => 3,
+            // Bobbles, nupps and custom stitches are worked into one stitch.
+            Stitch::Knit
+            | Stitch::Purl
+            | Stitch::Color(_)
+            | Stitch::Ktbl
+            | Stitch::Ptbl
+            | Stitch::SlWyib
+            | Stitch::SlWyif
+            | Stitch::Bobble
+            | Stitch::Nupp
+            | Stitch::WrapTurn
+            | Stitch::Brk
+            | Stitch::Brp
+            | Stitch::Sl1yo
+            | Stitch::BrkYoBrk
+            | Stitch::Custom(_) => 1,
+        }
+    }
+
+    // The number of stitches this leaves on the right needle.
+    pub fn produces(self) -> usize {
+        match self {
+            Stitch::Empty => 0,
+            Stitch::BrkYoBrk => 3,
+            _ => 1,
+        }
+    }
+
+    // The stitch that looks like this one reflected left to right. Decreases lean the other
+    // way. There's no ssp, so p2tog stays as it is.
+    pub fn mirrored(self) -> Stitch {
+        match self {
+            Stitch::M1L => Stitch::M1R,
+            Stitch::M1R => Stitch::M1L,
+            Stitch::K2tog => Stitch::Ssk,
+            Stitch::Ssk => Stitch::K2tog,
+            Stitch::K3tog => Stitch::Sssk,
+            Stitch::Sssk => Stitch::K3tog,
+            Stitch::Brk2tog => Stitch::Brssk,
+            Stitch::Brssk => Stitch::Brk2tog,
+            stitch => stitch,
+        }
+    }
 }
 
 impl Debug for Stitch {
@@ -31,6 +233,31 @@ impl Debug for Stitch {
             Stitch::Knit => ".",
             Stitch::Purl => "*",
             Stitch::Empty => "#",
+            Stitch::Color(index) => return write!(f, "{}", index),
+            Stitch::Custom(index) => return write!(f, "&{}", index),
+            Stitch::Yo => "O",
+            Stitch::M1L => "(",
+            Stitch::M1R => ")",
+            Stitch::K2tog => "/",
+            Stitch::Ssk => "\\",
+            Stitch::P2tog => ",",
+            Stitch::K3tog => ">",
+            Stitch::Sssk => "<",
+            Stitch::Cdd => "^",
+            Stitch::Ktbl => "&",
+            Stitch::Ptbl => "%",
+            Stitch::SlWyib => "-",
+            Stitch::SlWyif => "_",
+            Stitch::Bobble => "@",
+            Stitch::Nupp => "$",
+            Stitch::WrapTurn => "!",
+            Stitch::Brk => "+",
+            Stitch::Brp => "~",
+            Stitch::Sl1yo => ":",
+            Stitch::Brk2tog => "]",
+            Stitch::Brssk => "[",
+            Stitch::Brk3tog => "}",
+            Stitch::BrkYoBrk => ";",
         };
 
         write!(f, "{}", ch)
@@ -39,14 +266,30 @@ impl Debug for Stitch {
 
 impl Chart {
     pub fn open(filename: &str) -> Result<Chart> {
+        Chart::open_with(filename, &[])
+    }
+
+    pub fn read(rdr: impl BufRead) -> Result<Chart> {
+        Chart::read_with(rdr, &[])
+    }
+
+    // Like open(), but each (name, value) in overrides replaces the header attribute of that name.
+    // Unlike in the header, an unknown name is an error, since it is most likely a typo.
+    pub fn open_with(filename: &str, overrides: &[(String, String)]) -> Result<Chart> {
         let file = File::open(filename)?;
         let rdr = BufReader::new(file);
-        Chart::read(rdr)
+        Chart::read_with(rdr, overrides)
     }
 
-    pub fn read(mut rdr: impl BufRead) -> Result<Chart> {
-        let hdr = Header::new(&mut rdr)?;
-        let attributes = Attributes::new(hdr)?;
+    pub fn read_with(mut rdr: impl BufRead, overrides: &[(String, String)]) -> Result<Chart> {
+        let mut header = Header::new(&mut rdr)?;
+        for (name, value) in overrides {
+            if !Attributes::is_name(name) {
+                return Err(ErrorKind::UnknownAttrName(name.clone()).into());
+            }
+            header.set_value(name, value);
+        }
+        let (attributes, custom) = Chart::attributes_and_custom(&header)?;
 
         let mut lines: Vec<String> = Vec::new();
         let mut line = String::new();
@@ -66,15 +309,64 @@ impl Chart {
             lines.push(line.trim_end_matches('\n').to_string());
         }
 
-        let (stitches, rows, cols) = Chart::read_stitches(&attributes, &mut lines)?;
-        let chart = Chart {
+        let mut stitches: Vec<Vec<Stitch>> = Vec::new();
+        for line in lines {
+            stitches.push(Chart::read_line(&attributes, &custom, &line)?)
+        }
+
+        Ok(Chart::from_parts(header, attributes, custom, stitches))
+    }
+
+    // Builds a chart from stitches that didn't come from a chart file, e.g. an imported image.
+    pub(crate) fn from_stitches(header: Header, stitches: Vec<Vec<Stitch>>) -> Result<Chart> {
+        let (attributes, custom) = Chart::attributes_and_custom(&header)?;
+        Ok(Chart::from_parts(header, attributes, custom, stitches))
+    }
+
+    fn attributes_and_custom(header: &Header) -> Result<(Attributes, Vec<CustomStitch>)> {
+        let mut attributes = Attributes::new(header.clone())?;
+        let custom = custom_stitches(header)?;
+        let custom_chars: Vec<char> = custom.iter().map(|c| c.ch).collect();
+        attributes.unbind_default_chars(header, &custom_chars);
+        Ok((attributes, custom))
+    }
+
+    fn from_parts(
+        header: Header,
+        attributes: Attributes,
+        custom: Vec<CustomStitch>,
+        mut stitches: Vec<Vec<Stitch>>,
+    ) -> Chart {
+        let mut warnings = duplicate_chars(&attributes, &custom);
+        if attributes.dpi <= 0.0 && (attributes.cell_mm > 0.0 || attributes.cell_inches > 0.0) {
+            warnings.push("A cell size in mm or inches needs a dpi. Using cell_size.".into());
+        }
+        let (rows, cols) = fix_problems(&attributes, &mut stitches, &mut warnings);
+
+        Chart {
+            header,
             attributes,
+            custom,
             stitches,
+            warnings,
             rows,
             cols,
-        };
+        }
+    }
 
-        Ok(chart)
+    // Writes the chart back out in .knit format.
+    pub fn write(&self, mut w: impl Write) -> Result<()> {
+        for line in self.header.lines() {
+            writeln!(w, "{}={}", line.name, line.value)?;
+        }
+        writeln!(w, "CHART")?;
+        for row in 0..self.rows {
+            let line: String = (0..self.cols)
+                .map(|col| self.stitch_char(self.stitch(row, col)))
+                .collect();
+            writeln!(w, "{}", line)?;
+        }
+        Ok(())
     }
 
     // TODO: I am addicted to macros. Make a passthrough macro that prevents writing lots of
@@ -91,31 +383,193 @@ impl Chart {
     pub fn grid_color(&self) -> CssColor {
         self.attributes.grid_color
     }
+    pub fn highlight_color(&self) -> CssColor {
+        self.attributes.highlight_color
+    }
+    // None if the header doesn't ask for major lines between rows.
+    pub fn major_row_lines(&self) -> Option<MajorLines> {
+        major_lines(
+            self.attributes.major_row_interval,
+            self.attributes.major_row_color,
+            self.attributes.major_row_width,
+        )
+    }
+    pub fn major_column_lines(&self) -> Option<MajorLines> {
+        major_lines(
+            self.attributes.major_column_interval,
+            self.attributes.major_column_color,
+            self.attributes.major_column_width,
+        )
+    }
     pub fn cell_size(&self) -> f64 {
         self.attributes.cell_size
     }
-    pub fn dot_size(&self) -> f64 { self.attributes.dot_size }
+    // Cells are cell_size pixels wide, or the physical cell size at the chart's dpi. Given a
+    // gauge, they are as tall as a stitch is in proportion to its width. Otherwise they are
+    // square.
+    pub fn cell_width(&self) -> f64 {
+        let attributes = &self.attributes;
+        match self.dpi() {
+            Some(dpi) if attributes.cell_mm > 0.0 => attributes.cell_mm / MM_PER_INCH * dpi,
+            Some(dpi) if attributes.cell_inches > 0.0 => attributes.cell_inches * dpi,
+            _ => attributes.cell_size,
+        }
+    }
+    pub fn cell_height(&self) -> f64 {
+        match self.gauge() {
+            Some((stitches, rows)) => self.cell_width() * stitches / rows,
+            None => self.cell_width(),
+        }
+    }
+    // Samples per pixel across and down. At least 1.
+    pub fn quality(&self) -> u32 {
+        self.attributes.quality.max(1)
+    }
+    pub fn dpi(&self) -> Option<f64> {
+        Some(self.attributes.dpi).filter(|dpi| *dpi > 0.0)
+    }
+    // The size of the rendered chart in pixels, before any captions.
+    pub fn pixel_size(&self) -> (u32, u32) {
+        (
+            (self.cols as f64 * self.cell_width()).round() as u32,
+            (self.rows as f64 * self.cell_height()).round() as u32,
+        )
+    }
+    // Stitches and rows per 10cm, if both are given.
+    pub fn gauge(&self) -> Option<(f64, f64)> {
+        let stitches = self.attributes.stitches_per_10cm;
+        let rows = self.attributes.rows_per_10cm;
+        if stitches > 0.0 && rows > 0.0 {
+            Some((stitches, rows))
+        } else {
+            None
+        }
+    }
+    pub fn yards_per_stitch(&self) -> f64 {
+        self.attributes.yards_per_stitch
+    }
+    pub fn swatch_grams(&self) -> f64 {
+        self.attributes.swatch_grams
+    }
+    pub fn max_float(&self) -> usize {
+        self.attributes.max_float
+    }
+    pub fn true_proportions(&self) -> bool {
+        self.attributes.true_proportions
+    }
+    pub fn bobbin_labels(&self) -> bool {
+        self.attributes.bobbin_labels
+    }
+    pub fn machine_contrast(&self) -> &str {
+        &self.attributes.machine_contrast
+    }
+    pub fn machine_invert(&self) -> bool {
+        self.attributes.machine_invert
+    }
+    pub fn machine_mirror(&self) -> bool {
+        self.attributes.machine_mirror
+    }
+    // dot_size is in proportion to cell_size, so dots keep their size in the cell when the
+    // cell size comes from the dpi.
+    pub fn dot_size(&self) -> f64 {
+        self.attributes.dot_size * self.cell_width() / self.attributes.cell_size
+    }
+
+    // Captions. Empty if not given.
+    pub fn title(&self) -> &str {
+        &self.attributes.title
+    }
+    pub fn designer(&self) -> &str {
+        &self.attributes.designer
+    }
+    pub fn copyright(&self) -> &str {
+        &self.attributes.copyright
+    }
+    pub fn notes(&self) -> &str {
+        &self.attributes.notes
+    }
+    pub fn size(&self) -> &str {
+        &self.attributes.size
+    }
+
+    pub fn in_the_round(&self) -> bool {
+        self.attributes.in_the_round
+    }
+    pub fn mode(&self) -> Mode {
+        self.attributes.mode
+    }
+
+    // The row's number in the pattern, counting from the bottom. Brioche rows are worked in
+    // two passes, dark then light, so a pass is named like "3 LC".
+    pub fn row_name(&self, row: usize) -> String {
+        let number = self.rows - row;
+        match self.mode() {
+            Mode::Brioche if number % 2 == 1 => format!("{} DC", self.pattern_row(row)),
+            Mode::Brioche => format!("{} LC", self.pattern_row(row)),
+            _ => number.to_string(),
+        }
+    }
+
+    // Whether the row is worked with the right side facing: every round, or the odd rows
+    // of a flat chart.
+    pub fn is_right_side(&self, row: usize) -> bool {
+        self.in_the_round() || self.pattern_row(row) % 2 == 1
+    }
+
+    // The row's number in the pattern, with both passes of a brioche row counted as one.
+    fn pattern_row(&self, row: usize) -> usize {
+        let number = self.rows - row;
+        match self.mode() {
+            Mode::Brioche => number.div_ceil(2),
+            _ => number,
+        }
+    }
+
+    // The colors used by Stitch::Color, along with the chars that stand for them in the chart.
+    pub fn palette(&self) -> &[(char, CssColor)] {
+        &self.attributes.palette
+    }
 
     pub fn stitch(&self, row: usize, col: usize) -> Stitch {
         self.stitches[row][col]
     }
 
-    fn read_stitches(
-        attributes: &Attributes,
-        lines: &mut Vec<String>,
-    ) -> Result<(Vec<Vec<Stitch>>, usize, usize)> {
-        let mut stitches_vec: Vec<Vec<Stitch>> = Vec::new();
+    // The character used for this stitch in the chart file.
+    pub fn stitch_char(&self, stitch: Stitch) -> char {
+        char_for_stitch(&self.attributes, &self.custom, stitch)
+    }
 
-        for line in lines {
-            stitches_vec.push(Chart::read_line(attributes, line)?)
+    // The stitches defined in the header, indexed by Stitch::Custom.
+    pub fn custom_stitches(&self) -> &[CustomStitch] {
+        &self.custom
+    }
+
+    // Like Stitch::name() and Stitch::abbreviation(), but with the header's names for custom
+    // stitches.
+    pub fn stitch_name(&self, stitch: Stitch) -> &str {
+        name_for_stitch(&self.custom, stitch)
+    }
+    pub fn abbreviation(&self, stitch: Stitch) -> &str {
+        match stitch {
+            Stitch::Custom(index) => &self.custom[index].abbreviation,
+            stitch => stitch.abbreviation(),
         }
+    }
 
-        let (rows, cols) = fix_problems(attributes, &mut stitches_vec);
+    pub(crate) fn header(&self) -> &Header {
+        &self.header
+    }
 
-        Ok((stitches_vec, rows, cols))
+    // Problems that were found, and fixed, while reading the chart.
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
     }
 
-    fn read_line(attributes: &Attributes, line: &str) -> Result<Vec<Stitch>> {
+    fn read_line(
+        attributes: &Attributes,
+        custom: &[CustomStitch],
+        line: &str,
+    ) -> Result<Vec<Stitch>> {
         let mut stitch_vec: Vec<Stitch> = Vec::new();
 
         for ch in line.chars() {
@@ -123,7 +577,19 @@ impl Chart {
                 c if c == attributes.knit => Stitch::Knit,
                 c if c == attributes.purl => Stitch::Purl,
                 c if c == attributes.empty => Stitch::Empty,
-                _ => return Err(ErrorKind::BadStitchChar.into()),
+                c => match attributes.palette.iter().position(|(pc, _)| *pc == c) {
+                    Some(index) => Stitch::Color(index),
+                    None => match BOUND_STITCHES
+                        .iter()
+                        .find(|s| char_for_stitch(attributes, custom, **s) == c)
+                    {
+                        Some(stitch) => *stitch,
+                        None => match custom.iter().position(|cs| cs.ch == c) {
+                            Some(index) => Stitch::Custom(index),
+                            None => return Err(ErrorKind::BadStitchChar.into()),
+                        },
+                    },
+                },
             };
             stitch_vec.push(stitch);
         }
@@ -132,38 +598,128 @@ impl Chart {
     }
 }
 
-fn warn(s: &str) {
-    eprintln!("{}", s);
+// Whether the char stands for a stitch in a chart that doesn't set any stitch chars.
+pub(crate) fn is_default_stitch_char(ch: char) -> bool {
+    let attributes = Attributes::default();
+    [Stitch::Knit, Stitch::Purl, Stitch::Empty]
+        .iter()
+        .chain(BOUND_STITCHES.iter())
+        .any(|stitch| char_for_stitch(&attributes, &[], *stitch) == ch)
+}
+
+fn char_for_stitch(attributes: &Attributes, custom: &[CustomStitch], stitch: Stitch) -> char {
+    match stitch {
+        Stitch::Knit => attributes.knit,
+        Stitch::Purl => attributes.purl,
+        Stitch::Empty => attributes.empty,
+        Stitch::Color(index) => attributes.palette[index].0,
+        Stitch::Yo => attributes.yo,
+        Stitch::M1L => attributes.m1l,
+        Stitch::M1R => attributes.m1r,
+        Stitch::K2tog => attributes.k2tog,
+        Stitch::Ssk => attributes.ssk,
+        Stitch::P2tog => attributes.p2tog,
+        Stitch::K3tog => attributes.k3tog,
+        Stitch::Sssk => attributes.sssk,
+        Stitch::Cdd => attributes.cdd,
+        Stitch::Ktbl => attributes.ktbl,
+        Stitch::Ptbl => attributes.ptbl,
+        Stitch::SlWyib => attributes.sl_wyib,
+        Stitch::SlWyif => attributes.sl_wyif,
+        Stitch::Bobble => attributes.bobble,
+        Stitch::Nupp => attributes.nupp,
+        Stitch::WrapTurn => attributes.wrap_turn,
+        Stitch::Brk => attributes.brk,
+        Stitch::Brp => attributes.brp,
+        Stitch::Sl1yo => attributes.sl1yo,
+        Stitch::Brk2tog => attributes.brk2tog,
+        Stitch::Brssk => attributes.brssk,
+        Stitch::Brk3tog => attributes.brk3tog,
+        Stitch::BrkYoBrk => attributes.brkyobrk,
+        Stitch::Custom(index) => custom[index].ch,
+    }
+}
+
+fn name_for_stitch(custom: &[CustomStitch], stitch: Stitch) -> &str {
+    match stitch {
+        Stitch::Custom(index) => &custom[index].name,
+        stitch => stitch.name(),
+    }
+}
+
+// Warns about chars that stand for more than one stitch. Only the first one can be read.
+fn duplicate_chars(attributes: &Attributes, custom: &[CustomStitch]) -> Vec<String> {
+    let mut stitches = vec![Stitch::Knit, Stitch::Purl, Stitch::Empty];
+    stitches.extend((0..attributes.palette.len()).map(Stitch::Color));
+    stitches.extend(BOUND_STITCHES.iter());
+    stitches.extend((0..custom.len()).map(Stitch::Custom));
+
+    let mut warnings = Vec::new();
+    for (i, first) in stitches.iter().enumerate() {
+        let ch = char_for_stitch(attributes, custom, *first);
+        if ch == UNBOUND_CHAR {
+            continue;
+        }
+        for second in &stitches[i + 1..] {
+            if char_for_stitch(attributes, custom, *second) == ch {
+                let first_name = name_for_stitch(custom, *first);
+                warnings.push(format!(
+                    "'{}' is used for both {} and {}. It will be read as {}.",
+                    ch,
+                    first_name,
+                    name_for_stitch(custom, *second),
+                    first_name
+                ));
+            }
+        }
+    }
+    warnings
 }
 
-fn fix_problems(attributes: &Attributes, mut stitches: &mut Vec<Vec<Stitch>>) -> (usize, usize) {
+fn major_lines(interval: usize, color: CssColor, width: f64) -> Option<MajorLines> {
+    if interval > 0 {
+        Some(MajorLines {
+            interval,
+            color,
+            width,
+        })
+    } else {
+        None
+    }
+}
+
+fn fix_problems(
+    attributes: &Attributes,
+    mut stitches: &mut Vec<Vec<Stitch>>,
+    warnings: &mut Vec<String>,
+) -> (usize, usize) {
     let cols = figure_out_cols(attributes, stitches);
     let rows = figure_out_rows(attributes, stitches);
 
-    fixup_rows(rows, &mut stitches);
-    fixup_cols(cols, &mut stitches);
+    fixup_rows(rows, &mut stitches, warnings);
+    fixup_cols(cols, &mut stitches, warnings);
 
     (rows, cols)
 }
 
-fn fixup_rows(rows: usize, stitches: &mut Vec<Vec<Stitch>>) {
+fn fixup_rows(rows: usize, stitches: &mut Vec<Vec<Stitch>>, warnings: &mut Vec<String>) {
     if rows < stitches.len() {
-        warn("You have more rows then you specified");
+        warnings.push("You have more rows then you specified".into());
     } else if rows > stitches.len() {
-        warn("You have too few rows. I'm adding some for you.");
+        warnings.push("You have too few rows. I'm adding some for you.".into());
         repeat_with(|| Vec::new())
             .take(rows - stitches.len())
             .for_each(|v| stitches.push(v));
     }
 }
 
-fn fixup_cols(cols: usize, stitches: &mut Vec<Vec<Stitch>>) {
+fn fixup_cols(cols: usize, stitches: &mut Vec<Vec<Stitch>>, warnings: &mut Vec<String>) {
     for row in stitches {
         if cols < row.len() {
-            warn("You have too many stitches in one row. Truncating");
+            warnings.push("You have too many stitches in one row. Truncating".into());
             row.truncate(cols);
         } else if cols > row.len() {
-            warn("You are missing stitches in one row. Adding knits.");
+            warnings.push("You are missing stitches in one row. Adding knits.".into());
             repeat(Stitch::Knit)
                 .take(cols - row.len())
                 .for_each(|s| row.push(s));
@@ -186,3 +742,213 @@ fn figure_out_cols(attributes: &Attributes, stitches: &Vec<Vec<Stitch>>) -> usiz
         attributes.columns
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SMALL: &str = "knit=.\npurl=*\nCHART\n.*.\n*.*\n";
+
+    #[test]
+    fn read_test() {
+        let chart = Chart::read(SMALL.as_bytes()).unwrap();
+
+        assert_eq!(2, chart.rows());
+        assert_eq!(3, chart.columns());
+        assert!(chart.warnings().is_empty());
+        assert_eq!('*', chart.stitch_char(chart.stitch(0, 1)));
+        assert_eq!('.', chart.stitch_char(chart.stitch(1, 1)));
+    }
+
+    #[test]
+    fn overrides_test() {
+        let overrides = vec![("purl".to_string(), "o".to_string())];
+        let chart = Chart::read_with("purl=*\nCHART\n.o.\n".as_bytes(), &overrides).unwrap();
+
+        assert_eq!('o', chart.stitch_char(chart.stitch(0, 1)));
+
+        for (name, value) in &[
+            ("cell_size", "abc"),
+            ("quality", "high"),
+            ("mode", "intarsia"),
+        ] {
+            let overrides = vec![(name.to_string(), value.to_string())];
+            match Chart::read_with(
+                "CHART
+.
+"
+                .as_bytes(),
+                &overrides,
+            ) {
+                Err(Error(ErrorKind::BadAttrValue(bad, _), _)) => assert_eq!(name, &bad),
+                _ => panic!("{}={} should be rejected", name, value),
+            }
+        }
+
+        // A misspelled name is rejected, but custom stitches can be added.
+        let overrides = vec![("cel_size".to_string(), "20".to_string())];
+        match Chart::read_with("CHART\n.\n".as_bytes(), &overrides) {
+            Err(Error(ErrorKind::UnknownAttrName(name), _)) => assert_eq!("cel_size", name),
+            _ => panic!("cel_size should be rejected"),
+        }
+        let overrides = vec![(
+            "stitch_bobble".to_string(),
+            "B | bobble | mb | dot 0.5 0.5 0.3".to_string(),
+        )];
+        assert!(Chart::read_with("CHART\nB\n".as_bytes(), &overrides).is_ok());
+    }
+
+    #[test]
+    fn warnings_test() {
+        let chart = Chart::read("columns=4\nCHART\n..\n".as_bytes()).unwrap();
+
+        assert_eq!(4, chart.columns());
+        assert_eq!(1, chart.warnings().len());
+    }
+
+    #[test]
+    fn palette_test() {
+        let chart = Chart::read("palette=A:white B:#000080\nCHART\nAB.\n".as_bytes()).unwrap();
+
+        assert_eq!(2, chart.palette().len());
+        assert_eq!(Stitch::Color(0), chart.stitch(0, 0));
+        assert_eq!(Stitch::Color(1), chart.stitch(0, 1));
+        assert_eq!(Stitch::Knit, chart.stitch(0, 2));
+        assert_eq!('B', chart.stitch_char(chart.stitch(0, 1)));
+    }
+
+    #[test]
+    fn gauge_test() {
+        let chart = Chart::read("cell_size=20\nCHART\n.\n".as_bytes()).unwrap();
+        assert_eq!(None, chart.gauge());
+        assert_eq!(20.0, chart.cell_height());
+
+        let chart = Chart::read(
+            "cell_size=20\nstitches_per_10cm=22\nrows_per_10cm=30\nCHART\n.\n".as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(Some((22.0, 30.0)), chart.gauge());
+        assert_eq!(20.0, chart.cell_width());
+        assert!((chart.cell_height() - 14.667).abs() < 0.001);
+    }
+
+    #[test]
+    fn dpi_test() {
+        let chart = Chart::read("cell_size=20\ncell_mm=5\nCHART\n...\n".as_bytes()).unwrap();
+        assert_eq!(None, chart.dpi());
+        assert_eq!(20.0, chart.cell_width());
+        assert_eq!(1, chart.warnings().len());
+
+        let chart =
+            Chart::read("cell_size=20\ndot_size=10\ncell_mm=5\ndpi=254\nCHART\n...\n".as_bytes())
+                .unwrap();
+        assert_eq!(Some(254.0), chart.dpi());
+        assert!((chart.cell_width() - 50.0).abs() < 1e-9);
+        assert!((chart.dot_size() - 25.0).abs() < 1e-9);
+        assert_eq!((150, 50), chart.pixel_size());
+        assert!(chart.warnings().is_empty());
+
+        let chart = Chart::read("cell_inches=0.25\ndpi=300\nCHART\n...\n".as_bytes()).unwrap();
+        assert_eq!(75.0, chart.cell_width());
+
+        assert!(Chart::read("cell_size=0\nCHART\n...\n".as_bytes()).is_err());
+        assert!(Chart::read("cell_size=-5\nCHART\n...\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn major_lines_test() {
+        let chart = Chart::read(
+            "major_row_interval=10\nmajor_row_color=red\nmajor_column_width=3\nCHART\n.\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(
+            Some(MajorLines {
+                interval: 10,
+                color: "red".parse().unwrap(),
+                width: 2.0,
+            }),
+            chart.major_row_lines()
+        );
+        assert_eq!(None, chart.major_column_lines());
+    }
+
+    #[test]
+    fn shaping_test() {
+        let chart = Chart::read("CHART\n./O\\^\n".as_bytes()).unwrap();
+
+        assert_eq!(Stitch::Knit, chart.stitch(0, 0));
+        assert_eq!(Stitch::K2tog, chart.stitch(0, 1));
+        assert_eq!(Stitch::Yo, chart.stitch(0, 2));
+        assert_eq!(Stitch::Ssk, chart.stitch(0, 3));
+        assert_eq!(Stitch::Cdd, chart.stitch(0, 4));
+        assert!(chart.warnings().is_empty());
+    }
+
+    #[test]
+    fn duplicate_chars_test() {
+        let chart = Chart::read("ssk=/\nk2tog=/\nCHART\n./\n".as_bytes()).unwrap();
+
+        // The first binding wins, with a warning.
+        assert_eq!(Stitch::K2tog, chart.stitch(0, 1));
+        assert_eq!(1, chart.warnings().len());
+
+        // A char set in the header takes over from a default one without a warning.
+        let chart = Chart::read("ssk=/\nCHART\n./\n".as_bytes()).unwrap();
+        assert_eq!(Stitch::Ssk, chart.stitch(0, 1));
+        assert!(chart.warnings().is_empty());
+    }
+
+    #[test]
+    fn set_chars_override_defaults() {
+        // '-' is the default sl_wyib char, which gives way to a purl char set in the header.
+        let chart = Chart::read("purl=-\nCHART\n.-\n".as_bytes()).unwrap();
+        assert!(chart.warnings().is_empty());
+        assert_eq!(Stitch::Purl, chart.stitch(0, 1));
+
+        let chart = Chart::read("palette=O:red X:blue\nCHART\nOX\n".as_bytes()).unwrap();
+        assert!(chart.warnings().is_empty());
+        assert_eq!(Stitch::Color(1), chart.stitch(0, 1));
+
+        // Two chars set in the header still clash.
+        let chart = Chart::read("purl=-\nsl_wyib=-\nCHART\n.-\n".as_bytes()).unwrap();
+        assert_eq!(1, chart.warnings().len());
+    }
+
+    #[test]
+    fn custom_stitch_test() {
+        let chart =
+            Chart::read("stitch_bobble=B | bobble | mb | dot 0.5 0.5 0.3\nCHART\n.B\n".as_bytes())
+                .unwrap();
+
+        assert_eq!(Stitch::Custom(0), chart.stitch(0, 1));
+        assert_eq!('B', chart.stitch_char(chart.stitch(0, 1)));
+        assert_eq!("bobble", chart.stitch_name(Stitch::Custom(0)));
+        assert_eq!("mb", chart.abbreviation(Stitch::Custom(0)));
+        assert_eq!("k", chart.abbreviation(Stitch::Knit));
+
+        assert!(Chart::read("stitch_bobble=B | bobble\nCHART\n.B\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn brioche_test() {
+        let chart = Chart::read("mode=brioche\nCHART\n~:\n:+\n+:\n".as_bytes()).unwrap();
+
+        assert_eq!(Stitch::Brk, chart.stitch(1, 1));
+        assert_eq!(Stitch::Brp, chart.stitch(0, 0));
+        assert_eq!(
+            vec!["2 DC", "1 LC", "1 DC"],
+            (0..3).map(|row| chart.row_name(row)).collect::<Vec<_>>()
+        );
+        assert_eq!("2", Chart::read(SMALL.as_bytes()).unwrap().row_name(0));
+    }
+
+    #[test]
+    fn write_test() {
+        let chart = Chart::read(SMALL.as_bytes()).unwrap();
+        let mut out = Vec::new();
+        chart.write(&mut out).unwrap();
+
+        assert_eq!(SMALL, String::from_utf8(out).unwrap());
+    }
+}
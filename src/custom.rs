@@ -0,0 +1,411 @@
+use crate::attributes::parse_char_name;
+use crate::errors::*;
+use crate::header::Header;
+
+// Header lines whose names start with this define custom stitches, e.g.
+//   stitch_bobble=B | bobble | mb | circle 0.5 0.5 0.3; dot 0.5 0.5 0.1
+// The fields are the char used in the chart, the stitch's name, its abbreviation in written
+// instructions, and its symbol.
+pub(crate) const STITCH_PREFIX: &str = "stitch_";
+
+// Curves in paths are drawn as this many straight lines.
+const CURVE_SEGMENTS: usize = 8;
+
+// Arcs are drawn as straight lines that each turn at most this many degrees.
+const ARC_STEP_DEGREES: f64 = 15.0;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CustomStitch {
+    pub ch: char,
+    pub name: String,
+    pub abbreviation: String,
+    pub symbol: Vec<Shape>,
+}
+
+// Part of a custom stitch's symbol. Points are fractions of the cell's width and height, with
+// (0, 0) at the top left. Radii are fractions of the width across and of the height down.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Shape {
+    Circle { center: [f64; 2], radius: f64 },
+    Dot { center: [f64; 2], radius: f64 },
+    // Lines, arcs and paths. Curves are split into short straight lines.
+    Polyline(Vec<[f64; 2]>),
+}
+
+// The custom stitches defined in the header, in the order they appear.
+pub(crate) fn custom_stitches(header: &Header) -> Result<Vec<CustomStitch>> {
+    header
+        .lines()
+        .into_iter()
+        .filter(|line| line.name.starts_with(STITCH_PREFIX))
+        .map(|line| {
+            parse_custom_stitch(&line.value)
+                .map_err(|reason| ErrorKind::BadCustomStitch(line.name.clone(), reason).into())
+        })
+        .collect()
+}
+
+fn parse_custom_stitch(s: &str) -> std::result::Result<CustomStitch, String> {
+    let fields: Vec<&str> = s.split('|').map(str::trim).collect();
+    if fields.len() != 4 {
+        return Err("It should have the form 'char | name | abbreviation | symbol'.".into());
+    }
+    if fields[1].is_empty() || fields[2].is_empty() {
+        return Err("The name and abbreviation cannot be empty.".into());
+    }
+
+    let mut symbol = Vec::new();
+    for primitive in fields[3].split(';').map(str::trim) {
+        if !primitive.is_empty() {
+            symbol.extend(parse_primitive(primitive)?);
+        }
+    }
+
+    Ok(CustomStitch {
+        ch: parse_char_name(fields[0])?,
+        name: fields[1].to_string(),
+        abbreviation: fields[2].to_string(),
+        symbol,
+    })
+}
+
+// One of
+//   line x1 y1 x2 y2
+//   circle cx cy r
+//   dot cx cy r
+//   arc cx cy r start end, with angles in degrees clockwise from 3 o'clock
+//   path d, with d as in an SVG path
+fn parse_primitive(s: &str) -> std::result::Result<Vec<Shape>, String> {
+    let mut splits = s.splitn(2, char::is_whitespace);
+    let kind = splits.next().unwrap_or_default();
+    let rest = splits.next().unwrap_or_default().trim();
+    if kind == "path" {
+        return parse_path(rest);
+    }
+
+    let numbers = rest
+        .split_whitespace()
+        .map(|n| {
+            n.parse::<f64>()
+                .map_err(|_| format!("'{}' is not a number in '{}'.", n, s))
+        })
+        .collect::<std::result::Result<Vec<f64>, String>>()?;
+    let expected = match kind {
+        "line" => 4,
+        "circle" | "dot" => 3,
+        "arc" => 5,
+        _ => {
+            return Err(format!(
+                "'{}' is not a line, circle, dot, arc or path.",
+                kind
+            ))
+        }
+    };
+    if numbers.len() != expected {
+        return Err(format!("'{}' should have {} numbers.", s, expected));
+    }
+
+    let shape = match kind {
+        "line" => Shape::Polyline(vec![[numbers[0], numbers[1]], [numbers[2], numbers[3]]]),
+        "circle" => Shape::Circle {
+            center: [numbers[0], numbers[1]],
+            radius: numbers[2],
+        },
+        "dot" => Shape::Dot {
+            center: [numbers[0], numbers[1]],
+            radius: numbers[2],
+        },
+        _ => arc([numbers[0], numbers[1]], numbers[2], numbers[3], numbers[4]),
+    };
+    Ok(vec![shape])
+}
+
+pub(crate) fn arc(center: [f64; 2], radius: f64, start: f64, end: f64) -> Shape {
+    let segments = ((end - start).abs() / ARC_STEP_DEGREES).ceil().max(1.0) as usize;
+    let points = (0..=segments)
+        .map(|i| {
+            let angle = (start + (end - start) * i as f64 / segments as f64).to_radians();
+            [
+                center[0] + radius * angle.cos(),
+                center[1] + radius * angle.sin(),
+            ]
+        })
+        .collect();
+    Shape::Polyline(points)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PathToken {
+    Command(char),
+    Number(f64),
+}
+
+fn tokenize_path(d: &str) -> std::result::Result<Vec<PathToken>, String> {
+    let chars: Vec<char> = d.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let ch = chars[i];
+        if ch.is_whitespace() || ch == ',' {
+            i += 1;
+        } else if ch.is_ascii_alphabetic() {
+            tokens.push(PathToken::Command(ch));
+            i += 1;
+        } else {
+            // Numbers needn't be separated, e.g. "0.5-0.25" or ".5.5".
+            let start = i;
+            if ch == '-' || ch == '+' {
+                i += 1;
+            }
+            let mut seen_point = false;
+            while i < chars.len() && (chars[i].is_ascii_digit() || (chars[i] == '.' && !seen_point))
+            {
+                seen_point |= chars[i] == '.';
+                i += 1;
+            }
+            if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                i += 1;
+                if i < chars.len() && (chars[i] == '-' || chars[i] == '+') {
+                    i += 1;
+                }
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+            }
+            let text: String = chars[start..i.max(start + 1)].iter().collect();
+            let number = text
+                .parse()
+                .map_err(|_| format!("'{}' is not a number in the path '{}'.", text, d))?;
+            tokens.push(PathToken::Number(number));
+        }
+    }
+    Ok(tokens)
+}
+
+// Supports the M, L, H, V, C, Q and Z commands, and their relative forms.
+fn parse_path(d: &str) -> std::result::Result<Vec<Shape>, String> {
+    let tokens = tokenize_path(d)?;
+    let mut shapes = Vec::new();
+    let mut points: Vec<[f64; 2]> = Vec::new();
+    let mut position = [0.0, 0.0];
+    let mut subpath_start = position;
+    let mut command: Option<char> = None;
+    let mut i = 0;
+
+    while i < tokens.len() {
+        // A command's arguments can be repeated without repeating the command.
+        let current = match tokens[i] {
+            PathToken::Command(ch) => {
+                i += 1;
+                ch
+            }
+            PathToken::Number(_) => {
+                command.ok_or_else(|| format!("The path '{}' must start with a command.", d))?
+            }
+        };
+        let origin = if current.is_ascii_lowercase() {
+            position
+        } else {
+            [0.0, 0.0]
+        };
+        let next_point = |i: &mut usize| -> std::result::Result<[f64; 2], String> {
+            match (tokens.get(*i), tokens.get(*i + 1)) {
+                (Some(PathToken::Number(x)), Some(PathToken::Number(y))) => {
+                    *i += 2;
+                    Ok([origin[0] + x, origin[1] + y])
+                }
+                _ => Err(format!(
+                    "'{}' is missing a point in the path '{}'.",
+                    current, d
+                )),
+            }
+        };
+
+        match current.to_ascii_uppercase() {
+            'M' => {
+                let point = next_point(&mut i)?;
+                finish_subpath(&mut points, &mut shapes);
+                points.push(point);
+                position = point;
+                subpath_start = point;
+                // Points after a move are lines.
+                command = Some(if current == 'm' { 'l' } else { 'L' });
+                continue;
+            }
+            'L' => {
+                let point = next_point(&mut i)?;
+                line_to(&mut points, position, point);
+                position = point;
+            }
+            'H' | 'V' => {
+                let value = match tokens.get(i) {
+                    Some(PathToken::Number(value)) => *value,
+                    _ => {
+                        return Err(format!(
+                            "'{}' is missing a number in the path '{}'.",
+                            current, d
+                        ))
+                    }
+                };
+                i += 1;
+                let point = if current.eq_ignore_ascii_case(&'h') {
+                    [origin[0] + value, position[1]]
+                } else {
+                    [position[0], origin[1] + value]
+                };
+                line_to(&mut points, position, point);
+                position = point;
+            }
+            'C' => {
+                let control1 = next_point(&mut i)?;
+                let control2 = next_point(&mut i)?;
+                let end = next_point(&mut i)?;
+                for step in 1..=CURVE_SEGMENTS {
+                    let t = step as f64 / CURVE_SEGMENTS as f64;
+                    let u = 1.0 - t;
+                    let point = [
+                        u * u * u * position[0]
+                            + 3.0 * u * u * t * control1[0]
+                            + 3.0 * u * t * t * control2[0]
+                            + t * t * t * end[0],
+                        u * u * u * position[1]
+                            + 3.0 * u * u * t * control1[1]
+                            + 3.0 * u * t * t * control2[1]
+                            + t * t * t * end[1],
+                    ];
+                    line_to(&mut points, position, point);
+                }
+                position = end;
+            }
+            'Q' => {
+                let control = next_point(&mut i)?;
+                let end = next_point(&mut i)?;
+                for step in 1..=CURVE_SEGMENTS {
+                    let t = step as f64 / CURVE_SEGMENTS as f64;
+                    let u = 1.0 - t;
+                    let point = [
+                        u * u * position[0] + 2.0 * u * t * control[0] + t * t * end[0],
+                        u * u * position[1] + 2.0 * u * t * control[1] + t * t * end[1],
+                    ];
+                    line_to(&mut points, position, point);
+                }
+                position = end;
+            }
+            'Z' => {
+                if !points.is_empty() {
+                    points.push(subpath_start);
+                }
+                finish_subpath(&mut points, &mut shapes);
+                position = subpath_start;
+                command = None;
+                continue;
+            }
+            _ => {
+                return Err(format!(
+                    "The path command '{}' is not supported in the path '{}'.",
+                    current, d
+                ))
+            }
+        }
+        command = Some(current);
+    }
+    finish_subpath(&mut points, &mut shapes);
+
+    Ok(shapes)
+}
+
+fn line_to(points: &mut Vec<[f64; 2]>, from: [f64; 2], to: [f64; 2]) {
+    if points.is_empty() {
+        points.push(from);
+    }
+    points.push(to);
+}
+
+// A lone point from a move draws nothing.
+fn finish_subpath(points: &mut Vec<[f64; 2]>, shapes: &mut Vec<Shape>) {
+    if points.len() > 1 {
+        shapes.push(Shape::Polyline(std::mem::take(points)));
+    }
+    points.clear();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io::BufReader;
+
+    #[test]
+    fn stitches_from_header() {
+        let header_str = "knit=.\nstitch_bobble=B | bobble | mb | circle 0.5 0.5 0.3; dot 0.5 0.5 0.1\nstitch_tbl=T | knit through the back loop | k1tbl | line 0.3 0.8 0.7 0.2\nCHART\n";
+        let hdr = Header::new(&mut BufReader::new(header_str.as_bytes())).unwrap();
+        let stitches = custom_stitches(&hdr).unwrap();
+
+        assert_eq!(2, stitches.len());
+        assert_eq!('B', stitches[0].ch);
+        assert_eq!("bobble", stitches[0].name);
+        assert_eq!("mb", stitches[0].abbreviation);
+        assert_eq!(
+            vec![
+                Shape::Circle {
+                    center: [0.5, 0.5],
+                    radius: 0.3
+                },
+                Shape::Dot {
+                    center: [0.5, 0.5],
+                    radius: 0.1
+                },
+            ],
+            stitches[0].symbol
+        );
+        assert_eq!(
+            vec![Shape::Polyline(vec![[0.3, 0.8], [0.7, 0.2]])],
+            stitches[1].symbol
+        );
+
+        let hdr = Header::new(&mut BufReader::new("stitch_x=B | bobble\n".as_bytes())).unwrap();
+        assert!(custom_stitches(&hdr).is_err());
+    }
+
+    #[test]
+    fn primitives() {
+        let arc = &parse_primitive("arc 0.5 0.5 0.25 0 90").unwrap()[0];
+        match arc {
+            Shape::Polyline(points) => {
+                assert_eq!(7, points.len());
+                assert_eq!([0.75, 0.5], points[0]);
+                let last = points[6];
+                assert!((last[0] - 0.5).abs() < 1e-9 && (last[1] - 0.75).abs() < 1e-9);
+            }
+            _ => panic!("an arc should be a polyline"),
+        }
+
+        assert!(parse_primitive("line 0 0 1").is_err());
+        assert!(parse_primitive("square 0 0 1").is_err());
+        assert!(parse_primitive("dot 0 0 x").is_err());
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(
+            vec![
+                Shape::Polyline(vec![[0.25, 0.75], [0.75, 0.75], [0.75, 0.25], [0.25, 0.75]]),
+                Shape::Polyline(vec![[0.5, 0.25], [0.75, 0.25], [0.75, 0.75]]),
+            ],
+            parse_path("M0.25,0.75 H0.75 V.25z m.25-.5 .25 0 l0 .5").unwrap()
+        );
+
+        let curve = parse_path("M 0 1 Q 0.5 0 1 1").unwrap();
+        match &curve[0] {
+            Shape::Polyline(points) => {
+                assert_eq!(CURVE_SEGMENTS + 1, points.len());
+                assert_eq!([1.0, 1.0], points[CURVE_SEGMENTS]);
+            }
+            _ => panic!("a curve should be a polyline"),
+        }
+
+        assert!(parse_path("0 0 L 1 1").is_err());
+        assert!(parse_path("M 0 0 A 1 1 0 0 0 1 1").is_err());
+        assert!(parse_path("M 0 0 L 1").is_err());
+    }
+}
@@ -0,0 +1,351 @@
+use crate::chart::{Chart, Stitch};
+
+#[derive(Debug, PartialEq)]
+pub struct CellChange {
+    // The cell's position in the new chart.
+    pub row: usize,
+    pub col: usize,
+    pub old: Stitch,
+    pub new: Stitch,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct AttributeChange {
+    pub name: String,
+    // None if the attribute is only in the other chart.
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+// The differences between two versions of a chart. Rows and columns are lined up so that as
+// few cells as possible change, so a row or column added or removed in the middle of the
+// chart only shows up as that row or column. Knitting charts are numbered from the bottom
+// right, so when it makes no difference, rows and columns are added or removed at the top
+// and left.
+#[derive(Debug, PartialEq)]
+pub struct ChartDiff {
+    pub cells: Vec<CellChange>,
+    // Indexes in the new chart.
+    pub added_rows: Vec<usize>,
+    pub added_columns: Vec<usize>,
+    // Indexes in the old chart.
+    pub removed_rows: Vec<usize>,
+    pub removed_columns: Vec<usize>,
+    // Sorted by name.
+    pub attributes: Vec<AttributeChange>,
+}
+
+impl ChartDiff {
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+            && self.added_rows.is_empty()
+            && self.added_columns.is_empty()
+            && self.removed_rows.is_empty()
+            && self.removed_columns.is_empty()
+            && self.attributes.is_empty()
+    }
+
+    // The (row, col) of every cell in the new chart that is changed or added.
+    pub fn new_cells(&self, new: &Chart) -> Vec<(usize, usize)> {
+        let mut cells: Vec<(usize, usize)> = self.cells.iter().map(|c| (c.row, c.col)).collect();
+        for row in 0..new.rows() {
+            for col in 0..new.columns() {
+                if self.added_rows.contains(&row) || self.added_columns.contains(&col) {
+                    cells.push((row, col));
+                }
+            }
+        }
+        cells
+    }
+}
+
+// Compares the stitches themselves, so rebinding a stitch to another char in the header is
+// an attribute change, but not a cell change. Colors and custom stitches are compared by
+// what they are rather than their index, so reordering the palette doesn't change cells.
+pub fn diff_charts(old: &Chart, new: &Chart) -> ChartDiff {
+    // Start with the columns lined up on the right, then line up the rows by their cells in
+    // those columns, and the columns by their cells in those rows. Once more for the rows
+    // takes in any columns that moved.
+    let mut col_pairs = align(old.columns(), new.columns(), 0, |_, _| 0);
+    let row_pairs = align_rows(old, new, &col_pairs);
+    col_pairs = align_columns(old, new, &row_pairs);
+    let row_pairs = align_rows(old, new, &col_pairs);
+
+    let mut cells = Vec::new();
+    for (old_row, new_row) in matched(&row_pairs) {
+        for (old_col, new_col) in matched(&col_pairs) {
+            let old_stitch = old.stitch(old_row, old_col);
+            let new_stitch = new.stitch(new_row, new_col);
+            if !same_stitch(old, old_stitch, new, new_stitch) {
+                cells.push(CellChange {
+                    row: new_row,
+                    col: new_col,
+                    old: old_stitch,
+                    new: new_stitch,
+                });
+            }
+        }
+    }
+    cells.sort_by_key(|c| (c.row, c.col));
+
+    ChartDiff {
+        cells,
+        added_rows: only_new(&row_pairs),
+        added_columns: only_new(&col_pairs),
+        removed_rows: only_old(&row_pairs),
+        removed_columns: only_old(&col_pairs),
+        attributes: diff_attributes(old, new),
+    }
+}
+
+fn same_stitch(old: &Chart, old_stitch: Stitch, new: &Chart, new_stitch: Stitch) -> bool {
+    match (old_stitch, new_stitch) {
+        (Stitch::Color(a), Stitch::Color(b)) => old.palette()[a] == new.palette()[b],
+        (Stitch::Custom(a), Stitch::Custom(b)) => {
+            old.custom_stitches()[a] == new.custom_stitches()[b]
+        }
+        (a, b) => a == b,
+    }
+}
+
+// A line (row or column) of the old chart and the line it lines up with in the new one. One
+// side is None for a line that was removed or added.
+type Pair = (Option<usize>, Option<usize>);
+
+fn align_rows(old: &Chart, new: &Chart, col_pairs: &[Pair]) -> Vec<Pair> {
+    let cols: Vec<(usize, usize)> = matched(col_pairs).collect();
+    align(old.rows(), new.rows(), cols.len(), |a, b| {
+        cols.iter()
+            .filter(|(old_col, new_col)| {
+                !same_stitch(old, old.stitch(a, *old_col), new, new.stitch(b, *new_col))
+            })
+            .count()
+    })
+}
+
+fn align_columns(old: &Chart, new: &Chart, row_pairs: &[Pair]) -> Vec<Pair> {
+    let rows: Vec<(usize, usize)> = matched(row_pairs).collect();
+    align(old.columns(), new.columns(), rows.len(), |a, b| {
+        rows.iter()
+            .filter(|(old_row, new_row)| {
+                !same_stitch(old, old.stitch(*old_row, a), new, new.stitch(*new_row, b))
+            })
+            .count()
+    })
+}
+
+// Lines up old_len lines with new_len lines of length cells, where changes(old, new) is how
+// many cells differ between two lines, for the fewest changed cells (an edit distance).
+// Adding or removing a line costs more than changing all of its cells, so lines are only
+// added or removed when the lengths differ or it lines up the rest. When it makes no
+// difference, they are added or removed at the start.
+fn align(
+    old_len: usize,
+    new_len: usize,
+    cells: usize,
+    changes: impl Fn(usize, usize) -> usize,
+) -> Vec<Pair> {
+    let add_or_remove = cells + 1;
+    // cost[i][j] is the fewest changes that turn old[i..] into new[j..].
+    let mut cost = vec![vec![0; new_len + 1]; old_len + 1];
+    for i in (0..=old_len).rev() {
+        for j in (0..=new_len).rev() {
+            cost[i][j] = if i == old_len {
+                (new_len - j) * add_or_remove
+            } else if j == new_len {
+                (old_len - i) * add_or_remove
+            } else {
+                (cost[i + 1][j + 1] + changes(i, j))
+                    .min(cost[i + 1][j] + add_or_remove)
+                    .min(cost[i][j + 1] + add_or_remove)
+            };
+        }
+    }
+
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old_len || j < new_len {
+        if j == new_len || (i < old_len && cost[i][j] == cost[i + 1][j] + add_or_remove) {
+            pairs.push((Some(i), None));
+            i += 1;
+        } else if i == old_len || cost[i][j] == cost[i][j + 1] + add_or_remove {
+            pairs.push((None, Some(j)));
+            j += 1;
+        } else {
+            pairs.push((Some(i), Some(j)));
+            i += 1;
+            j += 1;
+        }
+    }
+    pairs
+}
+
+fn matched(pairs: &[Pair]) -> impl Iterator<Item = (usize, usize)> + '_ {
+    pairs.iter().filter_map(|pair| match pair {
+        (Some(i), Some(j)) => Some((*i, *j)),
+        _ => None,
+    })
+}
+
+fn only_new(pairs: &[Pair]) -> Vec<usize> {
+    pairs
+        .iter()
+        .filter_map(|pair| match pair {
+            (None, Some(j)) => Some(*j),
+            _ => None,
+        })
+        .collect()
+}
+
+fn only_old(pairs: &[Pair]) -> Vec<usize> {
+    pairs
+        .iter()
+        .filter_map(|pair| match pair {
+            (Some(i), None) => Some(*i),
+            _ => None,
+        })
+        .collect()
+}
+
+fn diff_attributes(old: &Chart, new: &Chart) -> Vec<AttributeChange> {
+    let mut names: Vec<&str> = old
+        .header()
+        .iter()
+        .chain(new.header().iter())
+        .map(|(name, _)| name.as_str())
+        .collect();
+    names.sort();
+    names.dedup();
+
+    names
+        .into_iter()
+        .map(|name| AttributeChange {
+            name: name.into(),
+            old: old.header().value(name).map(String::from),
+            new: new.header().value(name).map(String::from),
+        })
+        .filter(|change| change.old != change.new)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn chart(s: &str) -> Chart {
+        Chart::read(s.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn same_size() {
+        let old = chart("cell_size=15\nCHART\n...\n.X.\n");
+        let new = chart("cell_size=20\ntitle=Seed\nCHART\n...\n..X\n");
+        let diff = diff_charts(&old, &new);
+
+        assert_eq!(
+            vec![
+                CellChange {
+                    row: 1,
+                    col: 1,
+                    old: Stitch::Purl,
+                    new: Stitch::Knit,
+                },
+                CellChange {
+                    row: 1,
+                    col: 2,
+                    old: Stitch::Knit,
+                    new: Stitch::Purl,
+                },
+            ],
+            diff.cells
+        );
+        assert!(diff.added_rows.is_empty());
+        assert!(diff.removed_columns.is_empty());
+        assert_eq!(
+            vec![
+                AttributeChange {
+                    name: "cell_size".into(),
+                    old: Some("15".into()),
+                    new: Some("20".into()),
+                },
+                AttributeChange {
+                    name: "title".into(),
+                    old: None,
+                    new: Some("Seed".into()),
+                },
+            ],
+            diff.attributes
+        );
+    }
+
+    #[test]
+    fn added_and_removed() {
+        // A row is added at the top and a column removed from the left. The rest lines up.
+        let old = chart("CHART\nX..\nX.X\n");
+        let new = chart("CHART\nXX\n..\n.X\n");
+        let diff = diff_charts(&old, &new);
+
+        assert!(diff.cells.is_empty());
+        assert_eq!(vec![0], diff.added_rows);
+        assert!(diff.added_columns.is_empty());
+        assert!(diff.removed_rows.is_empty());
+        assert_eq!(vec![0], diff.removed_columns);
+        assert_eq!(vec![(0, 0), (0, 1)], diff.new_cells(&new));
+    }
+
+    #[test]
+    fn row_added_in_the_middle() {
+        let old = chart("CHART\nX..X\n.XX.\nXXXX\n");
+        let new = chart("CHART\nX..X\n....\n.XX.\nXX.X\n");
+        let diff = diff_charts(&old, &new);
+
+        assert_eq!(vec![1], diff.added_rows);
+        assert!(diff.removed_rows.is_empty());
+        assert!(diff.added_columns.is_empty());
+        assert!(diff.removed_columns.is_empty());
+        assert_eq!(
+            vec![CellChange {
+                row: 3,
+                col: 2,
+                old: Stitch::Purl,
+                new: Stitch::Knit,
+            }],
+            diff.cells
+        );
+    }
+
+    #[test]
+    fn column_removed_from_the_middle() {
+        let old = chart("CHART\nX.X.\n..XX\n.XX.\n");
+        let new = chart("CHART\nXX.\n.XX\n.X.\n");
+        let diff = diff_charts(&old, &new);
+
+        assert_eq!(vec![1], diff.removed_columns);
+        assert!(diff.added_columns.is_empty());
+        assert!(diff.added_rows.is_empty());
+        assert!(diff.removed_rows.is_empty());
+        assert!(diff.cells.is_empty());
+    }
+
+    #[test]
+    fn reordered_palette() {
+        let old = chart("palette=A:white B:navy\nCHART\nAB\n");
+        let new = chart("palette=B:navy A:white\nCHART\nAB\n");
+        let diff = diff_charts(&old, &new);
+
+        assert!(diff.cells.is_empty());
+        assert_eq!(1, diff.attributes.len());
+    }
+
+    #[test]
+    fn rebound_chars() {
+        let old = chart("CHART\n.X\n");
+        let new = chart("knit=k\npurl=p\nCHART\nkp\n");
+        let diff = diff_charts(&old, &new);
+
+        assert!(diff.cells.is_empty());
+        assert_eq!(2, diff.attributes.len());
+        assert!(!diff.is_empty());
+        assert!(diff_charts(&old, &old).is_empty());
+    }
+}
@@ -0,0 +1,42 @@
+use crate::chart::{Chart, Stitch};
+
+// Double knitting makes a fabric with two right sides. Each cell is the palette color that
+// shows on the front, and the back shows the other color, as a pair of stitches worked
+// together: a knit for the side facing the knitter and a purl for the other side.
+
+// The other color of the two in the palette.
+pub fn other_color(chart: &Chart, index: usize) -> usize {
+    if chart.palette().len() == 2 {
+        1 - index
+    } else {
+        index
+    }
+}
+
+// The stitch that shows at (row, col) of the back, seen from behind. The back is the front
+// reflected left to right, in the other colors.
+pub fn back_stitch(chart: &Chart, row: usize, col: usize) -> Stitch {
+    match chart.stitch(row, chart.columns() - 1 - col) {
+        Stitch::Color(index) => Stitch::Color(other_color(chart, index)),
+        stitch => stitch.mirrored(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn back() {
+        let chart = Chart::read(
+            "mode=double_knitting\npalette=A:black B:white\nCHART\nAAB\nAB/\n".as_bytes(),
+        )
+        .unwrap();
+
+        assert!(chart.warnings().is_empty());
+        assert_eq!(Stitch::Color(0), back_stitch(&chart, 0, 0));
+        assert_eq!(Stitch::Color(1), back_stitch(&chart, 0, 2));
+        assert_eq!(Stitch::Ssk, back_stitch(&chart, 1, 0));
+        assert_eq!(Stitch::Color(0), back_stitch(&chart, 1, 1));
+    }
+}
@@ -0,0 +1,124 @@
+use crate::chart::{Chart, Stitch};
+
+// A strand of yarn carried across the back of the work.
+#[derive(Debug, PartialEq)]
+pub struct Float {
+    pub row: usize,
+    // The leftmost column the float passes behind. In the round, a float may wrap around
+    // from the last column to the first.
+    pub start_col: usize,
+    // The number of stitches the float passes behind.
+    pub length: usize,
+    // The palette index of the floated color.
+    pub color: usize,
+}
+
+impl Float {
+    // The (row, col) of each cell the float passes behind.
+    pub fn cells(&self, chart: &Chart) -> Vec<(usize, usize)> {
+        let mut cells = Vec::new();
+        let mut col = self.start_col;
+        while cells.len() < self.length {
+            if chart.stitch(self.row, col) != Stitch::Empty {
+                cells.push((self.row, col));
+            }
+            col = (col + 1) % chart.columns();
+        }
+        cells
+    }
+}
+
+// Finds every float longer than max_length stitches in a colorwork chart. A color floats
+// from each stitch worked in it to the next one in the same row. Empty cells don't count
+// toward the length, since there is no stitch there.
+pub fn find_long_floats(chart: &Chart, max_length: usize) -> Vec<Float> {
+    let mut floats = Vec::new();
+    for row in 0..chart.rows() {
+        for color in 0..chart.palette().len() {
+            let worked: Vec<usize> = (0..chart.columns())
+                .filter(|col| chart.stitch(row, *col) == Stitch::Color(color))
+                .collect();
+
+            let mut gaps: Vec<(usize, usize)> = worked.windows(2).map(|w| (w[0], w[1])).collect();
+            if chart.in_the_round() {
+                if let (Some(first), Some(last)) = (worked.first(), worked.last()) {
+                    gaps.push((*last, *first + chart.columns()));
+                }
+            }
+
+            for (from, to) in gaps {
+                let length = (from + 1..to)
+                    .filter(|col| chart.stitch(row, col % chart.columns()) != Stitch::Empty)
+                    .count();
+                if length > max_length {
+                    floats.push(Float {
+                        row,
+                        start_col: (from + 1) % chart.columns(),
+                        length,
+                        color,
+                    });
+                }
+            }
+        }
+    }
+    floats
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn chart(header: &str, rows: &str) -> Chart {
+        let s = format!("palette=A:white B:navy\n{}\nCHART\n{}", header, rows);
+        Chart::read(s.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn flat_floats() {
+        let c = chart("", "BAAAAB\nBAABAA\n");
+        let floats = find_long_floats(&c, 3);
+
+        assert_eq!(
+            vec![Float {
+                row: 0,
+                start_col: 1,
+                length: 4,
+                color: 1,
+            }],
+            floats
+        );
+        assert_eq!(vec![(0, 1), (0, 2), (0, 3), (0, 4)], floats[0].cells(&c));
+        assert!(find_long_floats(&c, 4).is_empty());
+    }
+
+    #[test]
+    fn empty_cells_are_skipped() {
+        let c = chart("empty=#", "BAA##AAB\n");
+
+        assert_eq!(4, find_long_floats(&c, 3)[0].length);
+        assert!(find_long_floats(&c, 4).is_empty());
+    }
+
+    #[test]
+    fn floats_in_the_round() {
+        let c = chart("in_the_round=true", "AABAAA\n");
+        let floats = find_long_floats(&c, 3);
+
+        assert_eq!(
+            vec![Float {
+                row: 0,
+                start_col: 3,
+                length: 5,
+                color: 1,
+            }],
+            floats
+        );
+        assert_eq!(
+            vec![(0, 3), (0, 4), (0, 5), (0, 0), (0, 1)],
+            floats[0].cells(&c)
+        );
+
+        let flat = chart("", "AABAAA\n");
+        assert!(find_long_floats(&flat, 3).is_empty());
+    }
+}
@@ -5,7 +5,7 @@ use crate::errors::*;
 
 const CHART_STRING: &str = "CHART";
 
-#[derive(Default, Debug)]
+#[derive(Clone, Default, Debug)]
 pub struct Header {
     attributes: HashMap<String, Line>,
 }
@@ -13,7 +13,7 @@ pub struct Header {
 #[derive(Clone, Debug)]
 pub struct Line {
     line_number: usize,
-    name: String,
+    pub name: String,
     pub value: String,
 }
 
@@ -60,7 +60,31 @@ impl Header {
         self.attributes.iter()
     }
 
-    #[cfg(test)]
+    // Sets an attribute that did not come from the file, e.g. a command-line override.
+    // Overrides replace any value from the file and sort after it when written out.
+    pub fn set_value(&mut self, name: &str, value: &str) {
+        let line_number = self
+            .attributes
+            .get(name)
+            .map(|line| line.line_number)
+            .unwrap_or(usize::MAX);
+        self.attributes.insert(
+            name.into(),
+            Line {
+                line_number,
+                name: name.into(),
+                value: value.into(),
+            },
+        );
+    }
+
+    // The header lines in the order they should be written out.
+    pub fn lines(&self) -> Vec<&Line> {
+        let mut lines: Vec<&Line> = self.attributes.values().collect();
+        lines.sort_by(|a, b| (a.line_number, &a.name).cmp(&(b.line_number, &b.name)));
+        lines
+    }
+
     pub fn value(&self, name: &str) -> Option<&str> {
         if let Some(line) = self.attributes.get(name) {
             Some(&line.value)
@@ -201,6 +225,21 @@ mod test {
         assert_eq!("quux=quiggly", next_line);
     }
 
+    #[test]
+    fn set_value_test() {
+        let s = "foo=bar\nquux=bam\n";
+        let mut hdr = Header::new(&mut BufReader::new(s.as_bytes())).unwrap();
+        hdr.set_value("foo", "baz");
+        hdr.set_value("added", "new");
+
+        assert_eq!(3, hdr.num_attributes());
+        assert_eq!("baz", hdr.value("foo").unwrap());
+        assert_eq!("new", hdr.value("added").unwrap());
+
+        let names: Vec<&str> = hdr.lines().iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(vec!["foo", "quux", "added"], names);
+    }
+
     #[test]
     fn hr_new_test() {
         let mut brdr = BufReader::new("test".as_bytes());
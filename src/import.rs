@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+
+use image::RgbaImage;
+
+use crate::chart::{is_default_stitch_char, Chart, Stitch};
+use crate::errors::*;
+use crate::header::Header;
+
+// Chars used for the palette of an imported chart, in order, leaving out the default stitch
+// chars, like 'X' for purl and 'O' for yo.
+fn palette_chars() -> Vec<char> {
+    "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789"
+        .chars()
+        .filter(|ch| !is_default_stitch_char(*ch))
+        .collect()
+}
+
+// Pixels that are more transparent than this become Empty stitches.
+const ALPHA_THRESHOLD: u8 = 128;
+
+type Rgb = [u8; 3];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImportMode {
+    // Each pixel becomes a Stitch::Color from a palette of at most this many colors.
+    Colorwork(usize),
+    // Pixels darker than this luminance (0.0 - 1.0) become purls, the rest knits.
+    KnitPurl(f64),
+}
+
+// Makes a chart from an image (any format the image crate can read) with one pixel per stitch.
+pub fn import_image(bytes: &[u8], mode: ImportMode) -> Result<Chart> {
+    let image = image::load_from_memory(bytes)?.to_rgba();
+    import_rgba(&image, mode)
+}
+
+fn import_rgba(image: &RgbaImage, mode: ImportMode) -> Result<Chart> {
+    let mut header = Header::default();
+    let stitches = match mode {
+        ImportMode::Colorwork(max_colors) => {
+            let palette_chars = palette_chars();
+            let max_colors = max_colors.max(1).min(palette_chars.len());
+            let palette = quantize(&opaque_pixels(image), max_colors);
+
+            let entries: Vec<String> = palette
+                .iter()
+                .zip(palette_chars)
+                .map(|(rgb, ch)| format!("{}:#{:02x}{:02x}{:02x}", ch, rgb[0], rgb[1], rgb[2]))
+                .collect();
+            header.set_value("palette", &entries.join(" "));
+
+            map_pixels(image, |rgb| Stitch::Color(nearest(&palette, rgb)))
+        }
+        ImportMode::KnitPurl(threshold) => map_pixels(image, |rgb| {
+            if luminance(rgb) < threshold {
+                Stitch::Purl
+            } else {
+                Stitch::Knit
+            }
+        }),
+    };
+
+    Chart::from_stitches(header, stitches)
+}
+
+fn map_pixels(image: &RgbaImage, f: impl Fn(Rgb) -> Stitch) -> Vec<Vec<Stitch>> {
+    (0..image.height())
+        .map(|y| {
+            (0..image.width())
+                .map(|x| {
+                    let [r, g, b, a] = image.get_pixel(x, y).0;
+                    if a < ALPHA_THRESHOLD {
+                        Stitch::Empty
+                    } else {
+                        f([r, g, b])
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn opaque_pixels(image: &RgbaImage) -> Vec<Rgb> {
+    image
+        .pixels()
+        .filter(|p| p.0[3] >= ALPHA_THRESHOLD)
+        .map(|p| [p.0[0], p.0[1], p.0[2]])
+        .collect()
+}
+
+fn luminance(rgb: Rgb) -> f64 {
+    (0.2126 * f64::from(rgb[0]) + 0.7152 * f64::from(rgb[1]) + 0.0722 * f64::from(rgb[2])) / 255.0
+}
+
+fn distance(a: Rgb, b: Rgb) -> i32 {
+    a.iter()
+        .zip(b.iter())
+        .map(|(x, y)| (i32::from(*x) - i32::from(*y)).pow(2))
+        .sum()
+}
+
+fn nearest(palette: &[Rgb], rgb: Rgb) -> usize {
+    (0..palette.len())
+        .min_by_key(|index| distance(palette[*index], rgb))
+        .unwrap_or(0)
+}
+
+// Reduces the pixels to at most max_colors colors using median cut. The palette is
+// sorted with the most common color first.
+fn quantize(pixels: &[Rgb], max_colors: usize) -> Vec<Rgb> {
+    let mut counts = HashMap::<Rgb, usize>::new();
+    for pixel in pixels {
+        *counts.entry(*pixel).or_insert(0) += 1;
+    }
+
+    let mut boxes: Vec<Vec<(Rgb, usize)>> = vec![counts.into_iter().collect()];
+    while boxes.len() < max_colors {
+        // Split the box with the widest range of values in any one channel.
+        let (index, channel) = match boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(index, b)| {
+                let (channel, range) = widest_channel(b);
+                (index, channel, range)
+            })
+            .max_by_key(|(_, _, range)| *range)
+        {
+            Some((index, channel, _)) => (index, channel),
+            None => break,
+        };
+
+        let mut colors = boxes.swap_remove(index);
+        colors.sort_by_key(|(rgb, _)| rgb[channel]);
+        let total: usize = colors.iter().map(|(_, count)| count).sum();
+        let mut seen = 0;
+        let split = colors
+            .iter()
+            .position(|(_, count)| {
+                seen += count;
+                seen * 2 >= total
+            })
+            .map_or(1, |index| index + 1)
+            .max(1)
+            .min(colors.len() - 1);
+        let rest = colors.split_off(split);
+        boxes.push(colors);
+        boxes.push(rest);
+    }
+
+    let mut palette: Vec<(Rgb, usize)> = boxes
+        .iter()
+        .filter(|b| !b.is_empty())
+        .map(|b| average(b))
+        .collect();
+    palette.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    palette.into_iter().map(|(rgb, _)| rgb).collect()
+}
+
+fn widest_channel(colors: &[(Rgb, usize)]) -> (usize, u8) {
+    (0..3)
+        .map(|channel| {
+            let min = colors
+                .iter()
+                .map(|(rgb, _)| rgb[channel])
+                .min()
+                .unwrap_or(0);
+            let max = colors
+                .iter()
+                .map(|(rgb, _)| rgb[channel])
+                .max()
+                .unwrap_or(0);
+            (channel, max - min)
+        })
+        .max_by_key(|(_, range)| *range)
+        .unwrap_or((0, 0))
+}
+
+// The count-weighted average color of the box, and the total count.
+fn average(colors: &[(Rgb, usize)]) -> (Rgb, usize) {
+    let total: usize = colors.iter().map(|(_, count)| count).sum();
+    let mut rgb = [0u8; 3];
+    for (channel, value) in rgb.iter_mut().enumerate() {
+        let sum: usize = colors
+            .iter()
+            .map(|(c, count)| usize::from(c[channel]) * count)
+            .sum();
+        *value = ((sum + total / 2) / total) as u8;
+    }
+    (rgb, total)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use image::Rgba;
+
+    fn test_image() -> RgbaImage {
+        // A navy diamond on white, with a transparent corner.
+        RgbaImage::from_fn(3, 3, |x, y| match (x, y) {
+            (0, 0) => Rgba([0, 0, 0, 0]),
+            (1, _) | (_, 1) => Rgba([0, 0, 128, 255]),
+            _ => Rgba([255, 255, 255, 255]),
+        })
+    }
+
+    #[test]
+    fn quantize_few_colors() {
+        let pixels = vec![[1, 2, 3], [4, 5, 6], [4, 5, 6]];
+        assert_eq!(vec![[4, 5, 6], [1, 2, 3]], quantize(&pixels, 4));
+    }
+
+    #[test]
+    fn quantize_many_colors() {
+        let pixels: Vec<Rgb> = (0..50)
+            .map(|i| [i, i, i])
+            .chain((200..250).map(|i| [i, 0, 0]))
+            .collect();
+        let palette = quantize(&pixels, 2);
+
+        assert_eq!(2, palette.len());
+        assert_eq!(0, nearest(&palette, [10, 10, 10]));
+        assert_eq!(1, nearest(&palette, [240, 0, 0]));
+    }
+
+    #[test]
+    fn import_colorwork() {
+        let chart = import_rgba(&test_image(), ImportMode::Colorwork(8)).unwrap();
+
+        assert_eq!(3, chart.rows());
+        assert_eq!(3, chart.columns());
+        assert_eq!(2, chart.palette().len());
+        assert_eq!(Stitch::Empty, chart.stitch(0, 0));
+        assert_eq!(Stitch::Color(0), chart.stitch(1, 1));
+        assert_eq!(Stitch::Color(1), chart.stitch(2, 2));
+
+        let mut out = Vec::new();
+        chart.write(&mut out).unwrap();
+        assert_eq!(
+            "palette=A:#000080 B:#ffffff\nCHART\n AB\nAAA\nBAB\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn import_many_colors() {
+        let image = RgbaImage::from_fn(20, 1, |x, _| Rgba([x as u8 * 10, 0, 0, 255]));
+        let chart = import_rgba(&image, ImportMode::Colorwork(20)).unwrap();
+        assert_eq!(20, chart.palette().len());
+
+        // The chart reads back without clashes between palette and stitch chars.
+        let mut out = Vec::new();
+        chart.write(&mut out).unwrap();
+        let chart = Chart::read(out.as_slice()).unwrap();
+        assert!(chart.warnings().is_empty());
+        assert!(crate::shaping::count_stitches(&chart).mismatches.is_empty());
+        assert!(chart
+            .palette()
+            .iter()
+            .all(|(ch, _)| *ch != 'O' && *ch != 'X'));
+    }
+
+    #[test]
+    fn import_knit_purl() {
+        let chart = import_rgba(&test_image(), ImportMode::KnitPurl(0.5)).unwrap();
+
+        assert!(chart.palette().is_empty());
+        assert_eq!(Stitch::Purl, chart.stitch(0, 1));
+        assert_eq!(Stitch::Knit, chart.stitch(0, 2));
+    }
+}
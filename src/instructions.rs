@@ -0,0 +1,232 @@
+use crate::attributes::Mode;
+use crate::chart::{Chart, Stitch};
+use crate::double_knitting::other_color;
+use crate::mosaic::{row_color, worked_stitch};
+
+// One row of written instructions, e.g. "Row 2 (WS)" and "p2, k1, p2".
+#[derive(Debug, PartialEq)]
+pub struct WrittenRow {
+    pub label: String,
+    pub text: String,
+}
+
+// A stitch as it is written, with the palette char of its color for colorwork.
+#[derive(Clone, PartialEq)]
+struct Step {
+    abbreviation: String,
+    color: Option<char>,
+}
+
+// Row by row instructions, in the order the rows are knit, starting at the bottom of the chart.
+// In the round, every row is read from right to left. Knit flat, the odd rows are right side
+// rows, read from right to left, and the even rows are wrong side rows, read from left to
+// right and worked so that they look like the chart from the right side.
+//
+// Mosaic rows are labeled with their color, and the stitches in other colors are slipped.
+// Double knitting cells are written as pairs of stitches, starting with the side facing the
+// knitter, which is the back on wrong side rows. Each brioche row is two passes on the same
+// side, dark then light.
+pub fn written_instructions(chart: &Chart) -> Vec<WrittenRow> {
+    (0..chart.rows())
+        .rev()
+        .map(|row| {
+            let right_side = chart.is_right_side(row);
+            let mut label = if chart.in_the_round() {
+                format!("Round {}", chart.row_name(row))
+            } else if right_side {
+                format!("Row {} (RS)", chart.row_name(row))
+            } else {
+                format!("Row {} (WS)", chart.row_name(row))
+            };
+            if chart.mode() == Mode::Mosaic {
+                label += &format!(" with {}", chart.palette()[row_color(chart, row)].0);
+            }
+
+            let mut cols: Vec<usize> = (0..chart.columns()).collect();
+            if right_side {
+                cols.reverse();
+            }
+            let steps: Vec<Step> = cols
+                .into_iter()
+                .filter(|col| chart.stitch(row, *col) != Stitch::Empty)
+                .map(|col| match (chart.mode(), chart.stitch(row, col)) {
+                    (Mode::Mosaic, _) => step(chart, worked_stitch(chart, row, col), right_side),
+                    (Mode::DoubleKnitting, Stitch::Color(index)) => {
+                        paired_step(chart, index, right_side)
+                    }
+                    (_, stitch) => step(chart, stitch, right_side),
+                })
+                .collect();
+
+            WrittenRow {
+                label,
+                text: join_steps(&steps),
+            }
+        })
+        .collect()
+}
+
+fn step(chart: &Chart, stitch: Stitch, right_side: bool) -> Step {
+    let abbreviation = if right_side {
+        chart.abbreviation(stitch)
+    } else {
+        wrong_side_abbreviation(chart, stitch)
+    };
+    let color = match stitch {
+        Stitch::Color(index) => Some(chart.palette()[index].0),
+        _ => None,
+    };
+    Step {
+        abbreviation: abbreviation.to_string(),
+        color,
+    }
+}
+
+// A double knitting pair: a knit in the color of the side facing the knitter, and a purl in
+// the other color.
+fn paired_step(chart: &Chart, index: usize, right_side: bool) -> Step {
+    let (facing, away) = if right_side {
+        (index, other_color(chart, index))
+    } else {
+        (other_color(chart, index), index)
+    };
+    let palette = chart.palette();
+    Step {
+        abbreviation: format!("k1 {}, p1 {}", palette[facing].0, palette[away].0),
+        color: None,
+    }
+}
+
+// What to work on the wrong side so the stitch looks like its chart symbol from the right
+// side. Stitches without a wrong side form are written as they are.
+fn wrong_side_abbreviation(chart: &Chart, stitch: Stitch) -> &str {
+    match stitch {
+        Stitch::Knit | Stitch::Color(_) => "p",
+        Stitch::Purl => "k",
+        Stitch::K2tog => "p2tog",
+        Stitch::Ssk => "ssp",
+        Stitch::P2tog => "k2tog",
+        Stitch::K3tog => "p3tog",
+        Stitch::Sssk => "sssp",
+        Stitch::Ktbl => "p1tbl",
+        Stitch::Ptbl => "k1tbl",
+        // The yarn has to be on the other side to leave the float on the same side.
+        Stitch::SlWyib => "sl1 wyif",
+        Stitch::SlWyif => "sl1 wyib",
+        Stitch::Brk => "brp",
+        Stitch::Brp => "brk",
+        stitch => chart.abbreviation(stitch),
+    }
+}
+
+// Runs of the same stitch are written together: knits, purls and slips with a count, like
+// "k3" or "sl2 wyib", and anything else like "[k2tog] 2 times".
+fn join_steps(steps: &[Step]) -> String {
+    if steps.is_empty() {
+        return "no stitches".to_string();
+    }
+
+    let mut parts = Vec::new();
+    let mut i = 0;
+    while i < steps.len() {
+        let run = steps[i..].iter().take_while(|s| **s == steps[i]).count();
+        let step = &steps[i];
+        let abbreviation = step.abbreviation.as_str();
+        let mut part = if abbreviation == "k" || abbreviation == "p" {
+            format!("{}{}", abbreviation, run)
+        } else if abbreviation.starts_with("sl1 ") {
+            format!("sl{}{}", run, &abbreviation[3..])
+        } else if run == 1 {
+            abbreviation.to_string()
+        } else {
+            format!("[{}] {} times", abbreviation, run)
+        };
+        if let Some(color) = step.color {
+            part += &format!(" {}", color);
+        }
+        parts.push(part);
+        i += run;
+    }
+    parts.join(", ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn instructions(chart: &str) -> Vec<String> {
+        let chart = Chart::read(chart.as_bytes()).unwrap();
+        written_instructions(&chart)
+            .into_iter()
+            .map(|row| format!("{}: {}", row.label, row.text))
+            .collect()
+    }
+
+    #[test]
+    fn flat() {
+        assert_eq!(
+            vec![
+                "Row 1 (RS): k1, [k2tog] 2 times, yo, p2",
+                "Row 2 (WS): p1, ssp, k1, p3",
+            ],
+            instructions("CHART\n.\\X...\nXXO//.\n")
+        );
+        assert_eq!(
+            vec![
+                "Row 1 (RS): k1, sl2 wyib, [k1tbl] 2 times, mb",
+                "Row 2 (WS): k1tbl, sl1 wyib, p1, w&t, k2",
+            ],
+            instructions("CHART\n%_.!XX\n@&&--.\n")
+        );
+    }
+
+    #[test]
+    fn mosaic() {
+        assert_eq!(
+            vec![
+                "Row 1 (RS) with A: k4",
+                "Row 2 (WS) with A: p4",
+                "Row 3 (RS) with B: k1, sl2 wyib, k1",
+                "Row 4 (WS) with B: p1, sl2 wyif, p1",
+            ],
+            instructions("mode=mosaic\npalette=A:black B:white\nCHART\nBAAB\nBAAB\nAAAA\nAAAA\n")
+        );
+    }
+
+    #[test]
+    fn double_knitting() {
+        assert_eq!(
+            vec![
+                "Row 1 (RS): [k1 A, p1 B] 2 times, k1 B, p1 A",
+                "Row 2 (WS): [k1 B, p1 A] 2 times, k1 A, p1 B",
+            ],
+            instructions("mode=double_knitting\npalette=A:black B:white\nCHART\nAAB\nBAA\n")
+        );
+    }
+
+    #[test]
+    fn brioche() {
+        assert_eq!(
+            vec![
+                "Row 1 DC (RS): [brk] 2 times, sl1yo",
+                "Row 1 LC (RS): sl1yo, [brp] 2 times",
+                "Row 2 DC (WS): brp, sl1yo, brp",
+            ],
+            instructions("mode=brioche\nCHART\n+:+\n~~:\n:++\n")
+        );
+    }
+
+    #[test]
+    fn in_the_round() {
+        assert_eq!(
+            vec!["Round 1: k1 B, k2 A"],
+            instructions("in_the_round=true\npalette=A:white B:navy\nCHART\nAAB\n")
+        );
+        assert_eq!(
+            vec!["Round 1: mb, k1", "Round 2: no stitches"],
+            instructions(
+                "in_the_round=true\nstitch_bobble=B | bobble | mb | dot 0.5 0.5 0.3\nCHART\n  \n.B\n"
+            )
+        );
+    }
+}
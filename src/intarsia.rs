@@ -0,0 +1,130 @@
+use crate::chart::{Chart, Stitch};
+
+// A run of one color in a row, worked from one bobbin.
+#[derive(Debug, PartialEq)]
+pub struct Block {
+    pub row: usize,
+    // The leftmost and rightmost columns of the block.
+    pub start_col: usize,
+    pub end_col: usize,
+    // The palette index of the block's color.
+    pub color: usize,
+    pub bobbin: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Intarsia {
+    // The palette index of each bobbin's color. Bobbins are numbered from 0 in the order they
+    // are first used.
+    pub bobbins: Vec<usize>,
+    // In the order they are knit: from the bottom row up, and from right to left in each row.
+    pub blocks: Vec<Block>,
+}
+
+impl Intarsia {
+    // The number of bobbins needed of each palette color.
+    pub fn bobbins_per_color(&self, chart: &Chart) -> Vec<usize> {
+        let mut counts = vec![0; chart.palette().len()];
+        for color in &self.bobbins {
+            counts[*color] += 1;
+        }
+        counts
+    }
+}
+
+// Splits the colorwork into blocks, and works out the bobbins for them. A block carries on
+// with the bobbin of a block of the same color in the row below that it touches, even just
+// at a corner, since the yarn only has to move one stitch over. Each bobbin is only used for
+// one block in a row. Any other block starts a new bobbin.
+pub fn find_intarsia(chart: &Chart) -> Intarsia {
+    let mut bobbins = Vec::new();
+    let mut blocks: Vec<Block> = Vec::new();
+    // Where the blocks in the row below start.
+    let mut below = 0;
+
+    for row in (0..chart.rows()).rev() {
+        let row_start = blocks.len();
+        let mut col = chart.columns();
+        while col > 0 {
+            let stitch = chart.stitch(row, col - 1);
+            let run = (0..col)
+                .rev()
+                .take_while(|c| chart.stitch(row, *c) == stitch)
+                .count();
+            if let Stitch::Color(color) = stitch {
+                let (start_col, end_col) = (col - run, col - 1);
+                let continued = blocks[below..row_start]
+                    .iter()
+                    .find(|b| {
+                        b.color == color
+                            && b.start_col <= end_col + 1
+                            && start_col <= b.end_col + 1
+                            && !blocks[row_start..]
+                                .iter()
+                                .any(|used| used.bobbin == b.bobbin)
+                    })
+                    .map(|b| b.bobbin);
+                let bobbin = continued.unwrap_or_else(|| {
+                    bobbins.push(color);
+                    bobbins.len() - 1
+                });
+                blocks.push(Block {
+                    row,
+                    start_col,
+                    end_col,
+                    color,
+                    bobbin,
+                });
+            }
+            col -= run;
+        }
+        below = row_start;
+    }
+
+    Intarsia { bobbins, blocks }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn chart(rows: &str) -> Chart {
+        Chart::read(format!("palette=A:white B:red\nCHART\n{}", rows).as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn blocks_and_bobbins() {
+        let c = chart("AABBA\nABBAA\n");
+        let intarsia = find_intarsia(&c);
+
+        assert_eq!(vec![0, 1, 0], intarsia.bobbins);
+        assert_eq!(vec![2, 1], intarsia.bobbins_per_color(&c));
+        assert_eq!(
+            Block {
+                row: 1,
+                start_col: 3,
+                end_col: 4,
+                color: 0,
+                bobbin: 0,
+            },
+            intarsia.blocks[0]
+        );
+        // The B block moved over one stitch carries on with the same bobbin, as do the A blocks.
+        let row_0: Vec<_> = intarsia.blocks[3..]
+            .iter()
+            .map(|b| (b.start_col, b.end_col, b.bobbin))
+            .collect();
+        assert_eq!(vec![(4, 4, 0), (2, 3, 1), (0, 1, 2)], row_0);
+    }
+
+    #[test]
+    fn split_and_join() {
+        // A B block splits around the A, which needs a second B bobbin, and joins up again.
+        let c = chart("BBB\nBAB\nBBB\n");
+        let intarsia = find_intarsia(&c);
+
+        assert_eq!(vec![1, 0, 1], intarsia.bobbins);
+        let bobbins: Vec<_> = intarsia.blocks.iter().map(|b| b.bobbin).collect();
+        assert_eq!(vec![0, 0, 1, 2, 0], bobbins);
+    }
+}
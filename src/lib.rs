@@ -3,7 +3,24 @@ extern crate error_chain;
 
 mod attributes;
 mod chart;
+mod custom;
+mod diff;
+mod double_knitting;
+mod floats;
+mod font;
 mod header;
+mod import;
+mod instructions;
+mod intarsia;
+mod machine;
+mod measure;
+mod mosaic;
+mod punchcard;
+mod raster;
+mod render;
+mod repeat;
+mod shaping;
+mod symmetry;
 
 pub mod errors {
     error_chain! {
@@ -13,11 +30,31 @@ pub mod errors {
                 description("Bad stitch char")
                 display("Bad stitch char")
             }
+            BadAttrValue(name: String, reason: String) {
+                description("An attribute has a bad value.")
+                display("The attr {} has a bad value. {}", name, reason)
+            }
+            BadCustomStitch(name: String, reason: String) {
+                description("A custom stitch was badly defined.")
+                display("The custom stitch {} is badly defined. {}", name, reason)
+            }
+            BadPunchcardChart(reason: String) {
+                description("The chart can't be made into a punchcard.")
+                display("The chart can't be made into a punchcard. {}", reason)
+            }
             BadHeaderLine(line_number: usize) {
                 description("A badly formed header line was found")
                 display("Header line {} should have the form 'name=value'",
                         line_number)
             }
+            CannotWatchStdin {
+                description("Stdin cannot be watched for changes.")
+                display("Stdin cannot be watched for changes.")
+            }
+            CannotWatchToStdout {
+                description("Watched charts cannot be written to stdout.")
+                display("Watched charts cannot be written to stdout.")
+            }
             IdentInitialNotAlpha(line_number: usize) {
                 description("An identifier has an invalid first character.")
                 display("Identifier on line {} must start with alpha character.",
@@ -32,10 +69,23 @@ pub mod errors {
                 description("A char was badly named.")
                 display("A char was badly named.")
             }
+            LeaningCenterStitch(row: String) {
+                description("A leaning stitch is in the center column.")
+                display("Row {} has a leaning stitch in the center column, which can't be symmetric.",
+                        row)
+            }
             MissingIdent(line_number: usize) {
                 description("An identifier is missing in the header.")
                 display("Identifier missing on line {}.", line_number)
             }
+            OutputIsInput(path: String) {
+                description("The output would overwrite the input.")
+                display("{} is an input file, so it can't be written to.", path)
+            }
+            OutputNotADirectory(path: String) {
+                description("Several outputs were sent to a single file.")
+                display("{} must be a directory when there are several input files.", path)
+            }
             UnknownAttrName(name: String) {
                 description("Unknown attr name")
                 display("The attr {} is unknown.", name)
@@ -43,11 +93,32 @@ pub mod errors {
         }
         foreign_links {
             ColorParseError(css_color_parser::ColorParseError);
+            ImageError(image::ImageError);
             IoError(std::io::Error);
+            PngEncodingError(png::EncodingError);
             ParseIntError(std::num::ParseIntError);
             TryFromIntError(std::num::TryFromIntError);
         }
     }
 }
 
-pub use chart::{Chart, Stitch};
+pub use attributes::Mode;
+pub use chart::{Chart, MajorLines, Stitch};
+pub use custom::{CustomStitch, Shape};
+pub use diff::{diff_charts, AttributeChange, CellChange, ChartDiff};
+pub use double_knitting::{back_stitch, other_color};
+pub use floats::{find_long_floats, Float};
+pub use import::{import_image, ImportMode};
+pub use instructions::{written_instructions, WrittenRow};
+pub use intarsia::{find_intarsia, Block, Intarsia};
+pub use machine::{is_contrast, machine_image, write_machine_png};
+pub use measure::{measure, Measurements, YarnEstimate};
+pub use mosaic::{check_mosaic, row_color, MosaicError};
+pub use punchcard::{
+    punchcard, Punchcard, PunchcardRenderer, MIN_PUNCHCARD_ROWS, OVERLAP_ROWS, PUNCHCARD_WIDTH,
+};
+pub use raster::{rasterize, Progress};
+pub use render::{PngRenderer, RenderOptions, Renderer};
+pub use repeat::{find_repeat, Repeat};
+pub use shaping::{count_stitches, CountMismatch, RowCount, StitchCounts};
+pub use symmetry::{complete_from_left, find_symmetry, Symmetry, SymmetryBreak};
@@ -0,0 +1,108 @@
+use std::convert::TryFrom;
+use std::io::Write;
+
+use crate::chart::{Chart, Stitch};
+use crate::errors::*;
+
+// Whether the stitch is knit in the contrast yarn on a knitting machine: it is one of the
+// chart's machine_contrast chars, or if there are none, it isn't a knit, an empty cell or
+// the first palette color.
+pub fn is_contrast(chart: &Chart, stitch: Stitch) -> bool {
+    let contrast = chart.machine_contrast();
+    if contrast.trim().is_empty() {
+        !matches!(stitch, Stitch::Knit | Stitch::Empty | Stitch::Color(0))
+    } else {
+        let ch = chart.stitch_char(stitch);
+        !ch.is_whitespace() && contrast.contains(ch)
+    }
+}
+
+// One pixel per stitch, from the top of the chart down, with true for contrast. Mirrored if
+// the chart's machine_mirror is set.
+pub fn machine_image(chart: &Chart) -> Vec<Vec<bool>> {
+    (0..chart.rows())
+        .map(|row| {
+            let mut pixels: Vec<bool> = (0..chart.columns())
+                .map(|col| is_contrast(chart, chart.stitch(row, col)))
+                .collect();
+            if chart.machine_mirror() {
+                pixels.reverse();
+            }
+            pixels
+        })
+        .collect()
+}
+
+// Writes the machine image as a 1-bit grayscale PNG, with contrast black and main yarn
+// white, or the other way round if the chart's machine_invert is set.
+pub fn write_machine_png(chart: &Chart, w: &mut dyn Write) -> Result<()> {
+    let image = machine_image(chart);
+    let width = u32::try_from(chart.columns())?;
+    let height = u32::try_from(chart.rows())?;
+    let mut encoder = png::Encoder::new(w, width, height);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::One);
+
+    // Each row is packed 8 pixels to a byte, from the high bit, and padded to a whole byte.
+    let mut data = Vec::new();
+    for row in &image {
+        let mut bytes = vec![0u8; row.len().div_ceil(8)];
+        for (x, contrast) in row.iter().enumerate() {
+            if *contrast == chart.machine_invert() {
+                bytes[x / 8] |= 0x80 >> (x % 8);
+            }
+        }
+        data.extend(bytes);
+    }
+
+    let mut writer = encoder.write_header()?;
+    Ok(writer.write_image_data(&data)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn png_pixels(chart: &Chart) -> Vec<Vec<u8>> {
+        let mut png = Vec::new();
+        write_machine_png(chart, &mut png).unwrap();
+        let image = image::load_from_memory(&png).unwrap().to_luma();
+        (0..image.height())
+            .map(|y| {
+                (0..image.width())
+                    .map(|x| image.get_pixel(x, y)[0])
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn one_pixel_per_stitch() {
+        let chart =
+            Chart::read("palette=A:white B:red\nCHART\nABBAAAAAAB\nAAAAAAAAAA\n".as_bytes())
+                .unwrap();
+        assert_eq!(
+            vec![
+                vec![255, 0, 0, 255, 255, 255, 255, 255, 255, 0],
+                vec![255; 10],
+            ],
+            png_pixels(&chart)
+        );
+    }
+
+    #[test]
+    fn options() {
+        let chart = Chart::read(
+            "palette=A:white B:red\nmachine_invert=true\nmachine_mirror=true\nCHART\nABX\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(vec![vec![255, 255, 0]], png_pixels(&chart));
+
+        // Only purls are contrast.
+        let chart =
+            Chart::read("palette=A:white B:red\nmachine_contrast=X\nCHART\nABX.\n".as_bytes())
+                .unwrap();
+        assert_eq!(vec![vec![false, false, true, false]], machine_image(&chart));
+    }
+}
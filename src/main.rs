@@ -1,91 +1,1001 @@
-use std::convert::TryFrom;
-use std::convert::TryInto;
-use std::env;
-use std::io::Write;
-use std::path::PathBuf;
-
-use css_color_parser::Color as CssColor;
-use graphics::grid::Grid;
-use graphics::line::Line;
-use graphics_buffer::{RenderBuffer, IDENTITY};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
 use knitchart::errors::*;
-use knitchart::{Chart, Stitch};
+use knitchart::{
+    check_mosaic, complete_from_left, count_stitches, diff_charts, find_intarsia, find_long_floats,
+    find_repeat, find_symmetry, import_image, measure, write_machine_png, written_instructions,
+    Chart, Float, ImportMode, Mode, PngRenderer, PunchcardRenderer, RenderOptions, Renderer,
+    Stitch, SymmetryBreak,
+};
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(about = "Generate graphical knitting charts from ASCII charts.")]
+enum Command {
+    /// Render charts to image files.
+    Render {
+        #[structopt(flatten)]
+        common: Common,
+
+        /// Output file, or a directory to write the images into. Use - for stdout.
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// Image format to write: png, or punchcard for a printable 24 stitch punchcard.
+        #[structopt(long, default_value = "png")]
+        format: ImageFormat,
+
+        /// Resolution to print the image at, in dots per inch. Same as -D dpi=N.
+        #[structopt(long)]
+        dpi: Option<f64>,
+
+        /// Size of each cell: a length like 5mm, 0.5cm or 0.2in, which needs a dpi, or a number
+        /// of pixels.
+        #[structopt(long, parse(try_from_str = parse_cell_size))]
+        cell_size: Option<(String, String)>,
+
+        /// Draw each pixel from this many samples across and down, for smoother lines and dots.
+        /// Same as -D quality=N.
+        #[structopt(long)]
+        quality: Option<u32>,
+
+        /// Number the intarsia bobbins in the cells where they start. Same as
+        /// -D bobbin_labels=true.
+        #[structopt(long)]
+        bobbins: bool,
+
+        /// Keep running, and render again whenever an input file changes.
+        #[structopt(short, long)]
+        watch: bool,
+
+        /// Chart files to read. Use - for stdin.
+        #[structopt(parse(from_os_str), required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Print information about charts.
+    Info {
+        #[structopt(flatten)]
+        common: Common,
+
+        /// Chart files to read. Use - for stdin.
+        #[structopt(parse(from_os_str), required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Check charts for problems. Exits with an error if any are found.
+    Lint {
+        #[structopt(flatten)]
+        common: Common,
+
+        /// Chart files to read. Use - for stdin.
+        #[structopt(parse(from_os_str), required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Rewrite charts in another chart format.
+    Convert {
+        #[structopt(flatten)]
+        common: Common,
+
+        /// Output file, or a directory to write the charts into. Without one, the charts go
+        /// to stdout.
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// Chart format to write: knit, or machine for a 1-bit PNG with one pixel per stitch,
+        /// for electronic knitting machines.
+        #[structopt(long, default_value = "knit")]
+        format: ChartFormat,
+
+        /// Palette and stitch chars to knit in the contrast yarn, in machine images. Same as
+        /// -D machine_contrast=CHARS.
+        #[structopt(long)]
+        contrast: Option<String>,
+
+        /// Make the main yarn black and the contrast white, in machine images. Same as
+        /// -D machine_invert=true.
+        #[structopt(long)]
+        invert: bool,
+
+        /// Reflect machine images left to right. Same as -D machine_mirror=true.
+        #[structopt(long)]
+        mirror: bool,
+
+        /// Chart files to read. Use - for stdin.
+        #[structopt(parse(from_os_str), required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Make charts from pixel-art images (PNG or GIF), one pixel per stitch.
+    Import {
+        /// Only print errors.
+        #[structopt(short, long)]
+        quiet: bool,
+
+        /// Output file, or a directory to write the charts into. Use - for stdout.
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// The largest number of colors in the chart's palette.
+        #[structopt(long, default_value = "8")]
+        colors: usize,
+
+        /// Make a knit/purl chart instead, with dark pixels as purls and light pixels as knits.
+        #[structopt(long)]
+        knit_purl: bool,
+
+        /// Image files to read. Use - for stdin.
+        #[structopt(parse(from_os_str), required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Compare two versions of a chart. Exits with an error if they differ.
+    Diff {
+        #[structopt(flatten)]
+        common: Common,
+
+        /// Also write an image of the new chart with the changes highlighted. Use - for stdout,
+        /// which moves the list of changes to stderr.
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// The original chart. Use - for stdin.
+        #[structopt(parse(from_os_str))]
+        old: PathBuf,
+
+        /// The changed chart.
+        #[structopt(parse(from_os_str))]
+        new: PathBuf,
+    },
+    /// Find the smallest motif that each chart repeats.
+    Repeat {
+        #[structopt(flatten)]
+        common: Common,
 
-fn to_color_array(color: CssColor) -> graphics::types::Color {
-    use graphics::types::ColorComponent;
+        /// Also write the motif out as a chart, to this file or directory. Use - for stdout.
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
 
-    [
-        ColorComponent::from(color.r) / 255.0,
-        ColorComponent::from(color.g) / 255.0,
-        ColorComponent::from(color.b) / 255.0,
-        color.a,
-    ]
+        /// Chart files to read. Use - for stdin.
+        #[structopt(parse(from_os_str), required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Check whether charts are symmetric, and list the cells that aren't.
+    Symmetry {
+        #[structopt(flatten)]
+        common: Common,
+
+        /// Chart files to read. Use - for stdin.
+        #[structopt(parse(from_os_str), required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Complete charts by reflecting the left half onto the right half.
+    Mirror {
+        #[structopt(flatten)]
+        common: Common,
+
+        /// Output file, or a directory to write the charts into. Writes to stdout if not given.
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// Chart files to read. Use - for stdin.
+        #[structopt(parse(from_os_str), required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Print charts to the terminal.
+    Preview {
+        #[structopt(flatten)]
+        common: Common,
+
+        /// Chart files to read. Use - for stdin.
+        #[structopt(parse(from_os_str), required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Work out the intarsia blocks and bobbins for colorwork charts.
+    Intarsia {
+        #[structopt(flatten)]
+        common: Common,
+
+        /// Chart files to read. Use - for stdin.
+        #[structopt(parse(from_os_str), required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Print row by row written instructions for charts.
+    Instructions {
+        #[structopt(flatten)]
+        common: Common,
+
+        /// Chart files to read. Use - for stdin.
+        #[structopt(parse(from_os_str), required = true)]
+        files: Vec<PathBuf>,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+struct Common {
+    /// Only print errors.
+    #[structopt(short, long)]
+    quiet: bool,
+
+    /// Override a header attribute, e.g. -D cell_size=20.
+    #[structopt(short = "D", parse(try_from_str = parse_define), number_of_values = 1)]
+    defines: Vec<(String, String)>,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum ImageFormat {
+    Png,
+    Punchcard,
+}
+
+impl ImageFormat {
+    fn renderer(self) -> Box<dyn Renderer> {
+        match self {
+            ImageFormat::Png => Box::new(PngRenderer),
+            ImageFormat::Punchcard => Box::new(PunchcardRenderer),
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        self.renderer().extension()
+    }
 }
 
-fn the_thing(filename: &str, chart: &Chart) -> Result<()> {
-    let background_color = to_color_array(chart.background_color());
-    let dot_size = chart.dot_size();
-    let cell_size = chart.cell_size();
-    let grid_color = to_color_array(chart.grid_color());
+impl FromStr for ImageFormat {
+    type Err = String;
 
-    let rows = u32::try_from(chart.rows())?;
-    let cols = u32::try_from(chart.columns())?;
+    fn from_str(s: &str) -> std::result::Result<ImageFormat, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "png" => Ok(ImageFormat::Png),
+            "punchcard" => Ok(ImageFormat::Punchcard),
+            _ => Err(format!("'{}' is not a known image format.", s)),
+        }
+    }
+}
 
-    let cell_size_int = cell_size as u32;
-    let mut buffer = RenderBuffer::new(cols * cell_size_int, rows * cell_size_int);
+#[derive(Clone, Copy, Debug)]
+enum ChartFormat {
+    Knit,
+    Machine,
+}
 
-    buffer.clear(background_color);
+impl ChartFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ChartFormat::Knit => "knit",
+            ChartFormat::Machine => "png",
+        }
+    }
+}
 
-    let grid = Grid {
-        cols: cols,
-        rows: rows,
-        units: f64::from(cell_size),
+impl FromStr for ChartFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<ChartFormat, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "knit" => Ok(ChartFormat::Knit),
+            "machine" => Ok(ChartFormat::Machine),
+            _ => Err(format!("'{}' is not a known chart format.", s)),
+        }
+    }
+}
+
+fn parse_define(s: &str) -> std::result::Result<(String, String), String> {
+    let mut splits = s.splitn(2, '=');
+    match (splits.next(), splits.next()) {
+        (Some(name), Some(value)) if !name.trim().is_empty() => {
+            Ok((name.trim().into(), value.into()))
+        }
+        _ => Err(format!("'{}' should have the form 'name=value'", s)),
+    }
+}
+
+// Turns a cell size into the header attribute that sets it.
+fn parse_cell_size(s: &str) -> std::result::Result<(String, String), String> {
+    let (name, number, factor) = if let Some(n) = s.strip_suffix("mm") {
+        ("cell_mm", n, 1.0)
+    } else if let Some(n) = s.strip_suffix("cm") {
+        ("cell_mm", n, 10.0)
+    } else if let Some(n) = s.strip_suffix("in") {
+        ("cell_inches", n, 1.0)
+    } else {
+        ("cell_size", s.strip_suffix("px").unwrap_or(s), 1.0)
+    };
+    match number.trim().parse::<f64>() {
+        Ok(n) if n > 0.0 => Ok((name.into(), (n * factor).to_string())),
+        _ => Err(format!(
+            "'{}' is not a cell size, like 5mm, 0.2in or 20.",
+            s
+        )),
+    }
+}
+
+// "-" stands for stdin when reading and stdout when writing.
+fn is_stdio(path: &Path) -> bool {
+    path == Path::new("-")
+}
+
+fn create_output(path: &Path) -> Result<Box<dyn Write>> {
+    if is_stdio(path) {
+        Ok(Box::new(io::stdout()))
+    } else {
+        Ok(Box::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+// Where to write the output for `input`. If `output` is a directory, the file goes in there,
+// otherwise it is used as the file name. With no `output`, the file goes next to the input,
+// or to stdout if the input came from stdin. The output can't be the input file itself.
+fn output_path(input: &Path, output: Option<&Path>, extension: &str) -> Result<PathBuf> {
+    let name = if is_stdio(input) {
+        Path::new("stdin")
+    } else {
+        Path::new(input.file_name().unwrap_or_default())
+    };
+    let path = match output {
+        Some(dir) if dir.is_dir() => dir.join(name).with_extension(extension),
+        Some(file) => file.to_path_buf(),
+        None if is_stdio(input) => input.to_path_buf(),
+        None => input.with_extension(extension),
+    };
+    if !is_stdio(input) && same_file(input, &path) {
+        return Err(ErrorKind::OutputIsInput(path.to_string_lossy().into()).into());
+    }
+    Ok(path)
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+fn check_output(output: Option<&Path>, files: &[PathBuf]) -> Result<()> {
+    match output {
+        Some(path) if files.len() > 1 && !path.is_dir() => {
+            Err(ErrorKind::OutputNotADirectory(path.to_string_lossy().into()).into())
+        }
+        _ => Ok(()),
+    }
+}
+
+fn read_chart(file: &Path, common: &Common) -> Result<Chart> {
+    if is_stdio(file) {
+        let stdin = io::stdin();
+        let mut rdr = stdin.lock();
+        Chart::read_with(&mut rdr, &common.defines)
+    } else {
+        Chart::open_with(&file.to_string_lossy(), &common.defines)
+    }
+}
+
+fn open_chart(file: &Path, common: &Common) -> Result<Chart> {
+    let chart = read_chart(file, common)?;
+    if !common.quiet {
+        for warning in chart.warnings() {
+            eprintln!("{}: {}", file.to_string_lossy(), warning);
+        }
+    }
+    Ok(chart)
+}
+
+// Floats longer than the chart's max_float, if it has one.
+fn long_floats(chart: &Chart) -> Vec<Float> {
+    if chart.max_float() > 0 {
+        find_long_floats(chart, chart.max_float())
+    } else {
+        Vec::new()
+    }
+}
+
+fn float_cells(chart: &Chart) -> Vec<(usize, usize)> {
+    long_floats(chart)
+        .iter()
+        .flat_map(|float| float.cells(chart))
+        .collect()
+}
+
+fn render_file(
+    file: &Path,
+    common: &Common,
+    output: Option<&Path>,
+    format: ImageFormat,
+) -> Result<()> {
+    let chart = open_chart(file, common)?;
+    let outfile = output_path(file, output, format.extension())?;
+    // Keep stdout clean when the image is being written there.
+    let quiet = common.quiet || is_stdio(&outfile);
+    let highlights = float_cells(&chart);
+    // Progress goes to stderr, and only to a terminal, so it stays out of logs.
+    let show_progress = !common.quiet && io::stderr().is_terminal();
+    let mut progress = |done, total| {
+        eprint!("\rRendering row {} of {}", done, total);
     };
-    let line = Line::new(grid_color, 1.0);
-    grid.draw(&line, &Default::default(), IDENTITY, &mut buffer);
+    let options = RenderOptions {
+        highlights: &highlights,
+        progress: if show_progress {
+            Some(&mut progress)
+        } else {
+            None
+        },
+    };
+    let bytes = format.renderer().render_to_bytes(&chart, options)?;
+    if show_progress {
+        eprint!("\r{:40}\r", "");
+    }
+    if !quiet {
+        println!("Output file: {}", outfile.to_string_lossy());
+    }
+    let mut writer = create_output(&outfile)?;
+    writer.write_all(&bytes)?;
+    Ok(writer.flush()?)
+}
+
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
 
-    for cell in grid.cells() {
-        let (col, row) = cell;
-        let cell_pos = grid.cell_position(cell);
+fn modified_time(file: &Path) -> Option<SystemTime> {
+    fs::metadata(file).and_then(|m| m.modified()).ok()
+}
 
-        let center_y = cell_pos[0] + f64::from(cell_size) / 2.0;
-        let center_x = cell_pos[1] + f64::from(cell_size) / 2.0;
+// Polls the files forever, rendering each one again when its modification time changes.
+// Errors are reported, but don't stop the watch. Since nothing is written until the chart
+// has been parsed and rendered, a bad edit leaves the previous output in place.
+fn watch_files(
+    files: &[PathBuf],
+    common: &Common,
+    output: Option<&Path>,
+    format: ImageFormat,
+) -> Result<()> {
+    if files.iter().any(|f| is_stdio(f)) {
+        return Err(ErrorKind::CannotWatchStdin.into());
+    }
+    if output.is_some_and(is_stdio) {
+        return Err(ErrorKind::CannotWatchToStdout.into());
+    }
 
-        let stitch = chart.stitch(row.try_into()?, col.try_into()?);
+    // None until the file has been rendered once. After that, the file's modified time, or
+    // None if it is missing, so a missing file is reported once, when it goes missing.
+    let mut last_modified: Vec<Option<Option<SystemTime>>> = vec![None; files.len()];
+    loop {
+        for (file, last) in files.iter().zip(last_modified.iter_mut()) {
+            let modified = Some(modified_time(file));
+            if modified == *last {
+                continue;
+            }
+            *last = modified;
 
-        if let Stitch::Purl = stitch {
-            let rectangle = [
-                center_y - dot_size / 2.0,
-                center_x - dot_size / 2.0,
-                dot_size,
-                dot_size,
-            ];
-            std::io::stdout().flush()?;
-            graphics::ellipse([0.1, 0.1, 0.1, 1.0], rectangle, IDENTITY, &mut buffer);
+            if let Err(err) = render_file(file, common, output, format) {
+                report_error(&err);
+            }
         }
-        print!("\r{:?}          ", cell);
+        thread::sleep(WATCH_INTERVAL);
     }
-    print!("\r");
+}
 
-    let outfile = PathBuf::from(filename).with_extension("png");
-    println!("Output file: {}", outfile.to_string_lossy());
-    Ok(buffer.save(outfile)?)
+fn stitch_name(chart: &Chart, stitch: Stitch) -> String {
+    format!(
+        "{} ({})",
+        chart.stitch_name(stitch),
+        chart.stitch_char(stitch)
+    )
 }
 
-fn process_file(filename: &str) -> Result<()> {
-    let chart = Chart::open(filename)?;
-    println!("Chart: {}", filename);
+fn info_file(file: &Path, common: &Common) -> Result<()> {
+    let chart = open_chart(file, common)?;
+    let m = measure(&chart);
+
+    println!("Chart: {}", file.to_string_lossy());
     println!("     rows: {}", chart.rows());
     println!("  columns: {}", chart.columns());
+    if let (Some(width), Some(height)) = (m.width_cm, m.height_cm) {
+        println!("     size: {:.1} cm wide x {:.1} cm tall", width, height);
+    }
+
+    let counts = count_stitches(&chart);
+    println!("  cast on: {}", counts.cast_on);
+    println!(" bind off: {}", counts.bind_off);
 
-    the_thing(filename, &chart)?;
+    println!("  stitches:");
+    for (stitch, count) in &m.stitch_counts {
+        println!("    {}: {}", stitch_name(&chart, *stitch), count);
+    }
 
+    println!("  yarn:");
+    for estimate in &m.yarn {
+        let mut line = match estimate.color {
+            Some(index) => format!("    {}", stitch_name(&chart, Stitch::Color(index))),
+            None => "    main color".to_string(),
+        };
+        line += &format!(": {} stitches", estimate.stitches);
+        if let Some(yards) = estimate.yards {
+            line += &format!(", {:.1} yd", yards);
+        }
+        if let Some(grams) = estimate.grams {
+            line += &format!(", {:.1} g", grams);
+        }
+        println!("{}", line);
+    }
     Ok(())
 }
 
-fn main() -> Result<()> {
-    for file in env::args().skip(1) {
-        process_file(&file)?;
+// Returns the number of problems found in the file.
+fn lint_file(file: &Path, common: &Common) -> usize {
+    let name = file.to_string_lossy();
+    match read_chart(file, common) {
+        Ok(chart) => {
+            for warning in chart.warnings() {
+                println!("{}: {}", name, warning);
+            }
+            let floats = long_floats(&chart);
+            for float in &floats {
+                // Knitting charts number stitches from the right.
+                let cells = float.cells(&chart);
+                let first = cells.last().map_or(0, |(_, col)| chart.columns() - col);
+                let last = cells.first().map_or(0, |(_, col)| chart.columns() - col);
+                println!(
+                    "{}: row {}: {} floats across {} stitches, stitches {} to {}",
+                    name,
+                    chart.row_name(float.row),
+                    stitch_name(&chart, Stitch::Color(float.color)),
+                    float.length,
+                    first,
+                    last
+                );
+            }
+            let mismatches = count_stitches(&chart).mismatches;
+            for mismatch in &mismatches {
+                println!(
+                    "{}: row {}: works {} stitches, but the row before leaves {}",
+                    name,
+                    chart.row_name(mismatch.row),
+                    mismatch.found,
+                    mismatch.expected
+                );
+            }
+            let mosaic_errors = if chart.mode() == Mode::Mosaic {
+                check_mosaic(&chart)
+            } else {
+                Vec::new()
+            };
+            for error in &mosaic_errors {
+                println!(
+                    "{}: row {}: stitch {} is not in the row's color, or slipped from below",
+                    name,
+                    chart.row_name(error.row),
+                    chart.columns() - error.col
+                );
+            }
+            chart.warnings().len() + floats.len() + mismatches.len() + mosaic_errors.len()
+        }
+        Err(err) => {
+            println!("{}: {}", name, err);
+            1
+        }
+    }
+}
+
+fn convert_file(
+    file: &Path,
+    common: &Common,
+    output: Option<&Path>,
+    format: ChartFormat,
+) -> Result<()> {
+    let chart = open_chart(file, common)?;
+    let outfile = match output {
+        Some(output) => output_path(file, Some(output), format.extension())?,
+        None => PathBuf::from("-"),
+    };
+    if !common.quiet && !is_stdio(&outfile) {
+        println!("Output file: {}", outfile.to_string_lossy());
+    }
+    let mut writer = create_output(&outfile)?;
+    match format {
+        ChartFormat::Knit => chart.write(&mut writer)?,
+        ChartFormat::Machine => write_machine_png(&chart, &mut writer)?,
+    }
+    Ok(writer.flush()?)
+}
+
+fn import_file(file: &Path, quiet: bool, output: Option<&Path>, mode: ImportMode) -> Result<()> {
+    let mut bytes = Vec::new();
+    if is_stdio(file) {
+        io::stdin().read_to_end(&mut bytes)?;
+    } else {
+        File::open(file)?.read_to_end(&mut bytes)?;
+    }
+
+    let chart = import_image(&bytes, mode)?;
+    let outfile = output_path(file, output, "knit")?;
+    if !quiet && !is_stdio(&outfile) {
+        println!("Output file: {}", outfile.to_string_lossy());
+    }
+    let mut writer = create_output(&outfile)?;
+    chart.write(&mut writer)?;
+    Ok(writer.flush()?)
+}
+
+// Returns true if the charts differ.
+fn diff_files(
+    old_file: &Path,
+    new_file: &Path,
+    common: &Common,
+    output: Option<&Path>,
+) -> Result<bool> {
+    let old = open_chart(old_file, common)?;
+    let new = open_chart(new_file, common)?;
+    let diff = diff_charts(&old, &new);
+
+    // Rows and stitches are numbered from the bottom right, as in the charts.
+    let mut lines = Vec::new();
+    for row in &diff.removed_rows {
+        lines.push(format!("removed row {}", old.row_name(*row)));
+    }
+    for row in &diff.added_rows {
+        lines.push(format!("added row {}", new.row_name(*row)));
+    }
+    for col in &diff.removed_columns {
+        lines.push(format!("removed stitch {}", old.columns() - col));
+    }
+    for col in &diff.added_columns {
+        lines.push(format!("added stitch {}", new.columns() - col));
+    }
+    for change in &diff.cells {
+        lines.push(format!(
+            "row {}, stitch {}: {} -> {}",
+            new.row_name(change.row),
+            new.columns() - change.col,
+            stitch_name(&old, change.old),
+            stitch_name(&new, change.new)
+        ));
+    }
+    for change in &diff.attributes {
+        lines.push(match (&change.old, &change.new) {
+            (Some(old), Some(new)) => format!("changed {}: {} -> {}", change.name, old, new),
+            (None, Some(new)) => format!("added {}={}", change.name, new),
+            (Some(old), None) => format!("removed {}={}", change.name, old),
+            (None, None) => continue,
+        });
+    }
+
+    if let Some(outfile) = output {
+        let highlights = diff.new_cells(&new);
+        let options = RenderOptions {
+            highlights: &highlights,
+            progress: None,
+        };
+        let bytes = PngRenderer.render_to_bytes(&new, options)?;
+        let mut writer = create_output(outfile)?;
+        writer.write_all(&bytes)?;
+        writer.flush()?;
+    }
+    // Keep stdout clean when the image is being written there.
+    let image_to_stdout = match output {
+        Some(path) => is_stdio(path),
+        None => false,
+    };
+    for line in &lines {
+        if image_to_stdout {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
+    }
+    Ok(!diff.is_empty())
+}
+
+fn repeat_file(file: &Path, common: &Common, output: Option<&Path>) -> Result<()> {
+    let chart = open_chart(file, common)?;
+    let repeat = match find_repeat(&chart) {
+        Some(repeat) => repeat,
+        None => {
+            println!("{}: no repeat", file.to_string_lossy());
+            return Ok(());
+        }
+    };
+
+    if let Some(output) = output {
+        let outfile = output_path(file, Some(output), "knit")?;
+        let mut writer = create_output(&outfile)?;
+        repeat.motif(&chart)?.write(&mut writer)?;
+        writer.flush()?;
+        if is_stdio(&outfile) {
+            return Ok(());
+        }
+    }
+
+    println!(
+        "{}: repeat of {} stitches by {} rows",
+        file.to_string_lossy(),
+        repeat.width,
+        repeat.height
+    );
+    if repeat.col_offset > 0 || repeat.row_offset > 0 {
+        println!(
+            "  with part of a repeat in {} stitches on the left and {} rows on the top",
+            repeat.col_offset, repeat.row_offset
+        );
+    }
+    if repeat.col_scale > 1 || repeat.row_scale > 1 {
+        println!(
+            "  with each stitch of the motif drawn as a block {} wide and {} tall",
+            repeat.col_scale, repeat.row_scale
+        );
     }
     Ok(())
 }
+
+fn print_breaks(chart: &Chart, name: &str, breaks: &[SymmetryBreak]) {
+    if breaks.is_empty() {
+        println!("  {}", name);
+        return;
+    }
+    println!("  not {}:", name);
+    for b in breaks {
+        let ((row, col), (mirror_row, mirror_col)) = (b.cell, b.mirror);
+        if b.cell == b.mirror {
+            println!(
+                "    row {}, stitch {}: {} leans, in the center column",
+                chart.row_name(row),
+                chart.columns() - col,
+                stitch_name(chart, chart.stitch(row, col)),
+            );
+            continue;
+        }
+        println!(
+            "    row {}, stitch {}: {} doesn't match {} at row {}, stitch {}",
+            chart.row_name(row),
+            chart.columns() - col,
+            stitch_name(chart, chart.stitch(row, col)),
+            stitch_name(chart, chart.stitch(mirror_row, mirror_col)),
+            chart.row_name(mirror_row),
+            chart.columns() - mirror_col
+        );
+    }
+}
+
+fn symmetry_file(file: &Path, common: &Common) -> Result<()> {
+    let chart = open_chart(file, common)?;
+    let symmetry = find_symmetry(&chart);
+
+    println!("Chart: {}", file.to_string_lossy());
+    print_breaks(&chart, "left/right symmetric", &symmetry.left_right);
+    print_breaks(&chart, "top/bottom symmetric", &symmetry.top_bottom);
+    Ok(())
+}
+
+fn mirror_file(file: &Path, common: &Common, output: Option<&Path>) -> Result<()> {
+    let chart = complete_from_left(&open_chart(file, common)?)?;
+    let outfile = match output {
+        Some(output) => output_path(file, Some(output), "knit")?,
+        None => PathBuf::from("-"),
+    };
+    if !common.quiet && !is_stdio(&outfile) {
+        println!("Output file: {}", outfile.to_string_lossy());
+    }
+    let mut writer = create_output(&outfile)?;
+    chart.write(&mut writer)?;
+    Ok(writer.flush()?)
+}
+
+fn intarsia_file(file: &Path, common: &Common) -> Result<()> {
+    let chart = open_chart(file, common)?;
+    let intarsia = find_intarsia(&chart);
+
+    println!("Chart: {}", file.to_string_lossy());
+    println!("  bobbins:");
+    for (color, count) in intarsia.bobbins_per_color(&chart).iter().enumerate() {
+        println!(
+            "    {}: {}",
+            stitch_name(&chart, Stitch::Color(color)),
+            count
+        );
+    }
+
+    // Blocks are listed as they are knit, with stitches numbered from the right.
+    println!("  blocks:");
+    for row in (0..chart.rows()).rev() {
+        let blocks: Vec<String> = intarsia
+            .blocks
+            .iter()
+            .filter(|b| b.row == row)
+            .map(|b| {
+                format!(
+                    "{} stitches {} to {} (bobbin {})",
+                    chart.palette()[b.color].0,
+                    chart.columns() - b.end_col,
+                    chart.columns() - b.start_col,
+                    b.bobbin + 1
+                )
+            })
+            .collect();
+        if !blocks.is_empty() {
+            println!("    row {}: {}", chart.row_name(row), blocks.join(", "));
+        }
+    }
+    Ok(())
+}
+
+fn instructions_file(file: &Path, common: &Common) -> Result<()> {
+    let chart = open_chart(file, common)?;
+    for row in written_instructions(&chart) {
+        println!("{}: {}", row.label, row.text);
+    }
+    Ok(())
+}
+
+fn preview_file(file: &Path, common: &Common) -> Result<()> {
+    let chart = open_chart(file, common)?;
+    // Knitting charts number their rows from the bottom, on the right.
+    for row in 0..chart.rows() {
+        let line: String = (0..chart.columns())
+            .map(|col| chart.stitch_char(chart.stitch(row, col)))
+            .collect();
+        println!("{} {}", line, chart.row_name(row));
+    }
+    Ok(())
+}
+
+fn run() -> Result<()> {
+    match Command::from_args() {
+        Command::Render {
+            mut common,
+            output,
+            format,
+            dpi,
+            cell_size,
+            quality,
+            bobbins,
+            watch,
+            files,
+        } => {
+            if bobbins {
+                common.defines.push(("bobbin_labels".into(), "true".into()));
+            }
+            if let Some(quality) = quality {
+                common.defines.push(("quality".into(), quality.to_string()));
+            }
+            if let Some(dpi) = dpi {
+                common.defines.push(("dpi".into(), dpi.to_string()));
+            }
+            if let Some((name, value)) = cell_size {
+                // A length in the header would take precedence, so clear the ones not given.
+                for length in &["cell_mm", "cell_inches"] {
+                    if *length != name {
+                        common.defines.push((length.to_string(), "0".into()));
+                    }
+                }
+                common.defines.push((name, value));
+            }
+            check_output(output.as_deref(), &files)?;
+            if watch {
+                watch_files(&files, &common, output.as_deref(), format)?;
+            } else {
+                for file in &files {
+                    render_file(file, &common, output.as_deref(), format)?;
+                }
+            }
+        }
+        Command::Info { common, files } => {
+            for file in &files {
+                info_file(file, &common)?;
+            }
+        }
+        Command::Lint { common, files } => {
+            let problems: usize = files.iter().map(|f| lint_file(f, &common)).sum();
+            if problems > 0 {
+                std::process::exit(1);
+            }
+        }
+        Command::Convert {
+            mut common,
+            output,
+            format,
+            contrast,
+            invert,
+            mirror,
+            files,
+        } => {
+            if let Some(contrast) = contrast {
+                common.defines.push(("machine_contrast".into(), contrast));
+            }
+            if invert {
+                common
+                    .defines
+                    .push(("machine_invert".into(), "true".into()));
+            }
+            if mirror {
+                common
+                    .defines
+                    .push(("machine_mirror".into(), "true".into()));
+            }
+            check_output(output.as_deref(), &files)?;
+            for file in &files {
+                convert_file(file, &common, output.as_deref(), format)?;
+            }
+        }
+        Command::Import {
+            quiet,
+            output,
+            colors,
+            knit_purl,
+            files,
+        } => {
+            check_output(output.as_deref(), &files)?;
+            let mode = if knit_purl {
+                ImportMode::KnitPurl(0.5)
+            } else {
+                ImportMode::Colorwork(colors)
+            };
+            for file in &files {
+                import_file(file, quiet, output.as_deref(), mode)?;
+            }
+        }
+        Command::Diff {
+            common,
+            output,
+            old,
+            new,
+        } => {
+            if diff_files(&old, &new, &common, output.as_deref())? {
+                std::process::exit(1);
+            }
+        }
+        Command::Repeat {
+            common,
+            output,
+            files,
+        } => {
+            check_output(output.as_deref(), &files)?;
+            for file in &files {
+                repeat_file(file, &common, output.as_deref())?;
+            }
+        }
+        Command::Symmetry { common, files } => {
+            for file in &files {
+                symmetry_file(file, &common)?;
+            }
+        }
+        Command::Mirror {
+            common,
+            output,
+            files,
+        } => {
+            check_output(output.as_deref(), &files)?;
+            for file in &files {
+                mirror_file(file, &common, output.as_deref())?;
+            }
+        }
+        Command::Preview { common, files } => {
+            for file in &files {
+                preview_file(file, &common)?;
+            }
+        }
+        Command::Intarsia { common, files } => {
+            for file in &files {
+                intarsia_file(file, &common)?;
+            }
+        }
+        Command::Instructions { common, files } => {
+            for file in &files {
+                instructions_file(file, &common)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn report_error(err: &Error) {
+    eprintln!("Error: {}", err);
+    for cause in err.iter().skip(1) {
+        eprintln!("  caused by: {}", cause);
+    }
+}
+
+fn main() {
+    if let Err(err) = run() {
+        report_error(&err);
+        std::process::exit(1);
+    }
+}
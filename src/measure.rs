@@ -0,0 +1,132 @@
+use crate::chart::{Chart, Stitch};
+
+#[derive(Debug, PartialEq)]
+pub struct Measurements {
+    // Finished size of the charted piece. None if the chart has no gauge.
+    pub width_cm: Option<f64>,
+    pub height_cm: Option<f64>,
+
+    // How many of each kind of stitch, in the order they first appear in the chart.
+    pub stitch_counts: Vec<(Stitch, usize)>,
+
+    // One estimate for each yarn used, in the order they first appear in the chart.
+    pub yarn: Vec<YarnEstimate>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct YarnEstimate {
+    // The palette index of the color, or None for the main yarn of a knit/purl chart.
+    pub color: Option<usize>,
+    pub stitches: usize,
+    // From yards_per_stitch, if given.
+    pub yards: Option<f64>,
+    // From swatch_grams and the gauge, if given.
+    pub grams: Option<f64>,
+}
+
+pub fn measure(chart: &Chart) -> Measurements {
+    let gauge = chart.gauge();
+    let width_cm = gauge.map(|(stitches, _)| chart.columns() as f64 * 10.0 / stitches);
+    let height_cm = gauge.map(|(_, rows)| chart.rows() as f64 * 10.0 / rows);
+
+    let mut stitch_counts: Vec<(Stitch, usize)> = Vec::new();
+    let mut yarn_counts: Vec<(Option<usize>, usize)> = Vec::new();
+    for row in 0..chart.rows() {
+        for col in 0..chart.columns() {
+            let stitch = chart.stitch(row, col);
+            increment(&mut stitch_counts, stitch);
+
+            match stitch {
+                Stitch::Empty => {}
+                Stitch::Color(index) => increment(&mut yarn_counts, Some(index)),
+                _ => increment(&mut yarn_counts, None),
+            }
+        }
+    }
+
+    let yards_per_stitch = Some(chart.yards_per_stitch()).filter(|y| *y > 0.0);
+    let grams_per_stitch = match (chart.swatch_grams(), gauge) {
+        (grams, Some((stitches, rows))) if grams > 0.0 => Some(grams / (stitches * rows)),
+        _ => None,
+    };
+    let yarn = yarn_counts
+        .into_iter()
+        .map(|(color, stitches)| YarnEstimate {
+            color,
+            stitches,
+            yards: yards_per_stitch.map(|y| y * stitches as f64),
+            grams: grams_per_stitch.map(|g| g * stitches as f64),
+        })
+        .collect();
+
+    Measurements {
+        width_cm,
+        height_cm,
+        stitch_counts,
+        yarn,
+    }
+}
+
+fn increment<T: PartialEq>(counts: &mut Vec<(T, usize)>, key: T) {
+    match counts.iter_mut().find(|(k, _)| *k == key) {
+        Some((_, count)) => *count += 1,
+        None => counts.push((key, 1)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn measure_knit_purl() {
+        let chart = Chart::read(
+            "stitches_per_10cm=20\nrows_per_10cm=25\nyards_per_stitch=0.5\nCHART\n.X.\n X.\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        let m = measure(&chart);
+
+        assert_eq!(Some(1.5), m.width_cm);
+        assert_eq!(Some(0.8), m.height_cm);
+        assert_eq!(
+            vec![(Stitch::Knit, 3), (Stitch::Purl, 2), (Stitch::Empty, 1)],
+            m.stitch_counts
+        );
+        assert_eq!(
+            vec![YarnEstimate {
+                color: None,
+                stitches: 5,
+                yards: Some(2.5),
+                grams: None,
+            }],
+            m.yarn
+        );
+    }
+
+    #[test]
+    fn measure_colorwork() {
+        let chart = Chart::read(
+            "palette=A:white B:navy\nstitches_per_10cm=10\nrows_per_10cm=10\nswatch_grams=20\nCHART\nAAB\nABB\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        let m = measure(&chart);
+
+        assert_eq!(2, m.yarn.len());
+        assert_eq!(Some(0), m.yarn[0].color);
+        assert_eq!(3, m.yarn[0].stitches);
+        assert!((m.yarn[0].grams.unwrap() - 0.6).abs() < 1e-9);
+        assert_eq!(Some(1), m.yarn[1].color);
+        assert_eq!(None, m.yarn[1].yards);
+    }
+
+    #[test]
+    fn measure_without_gauge() {
+        let chart = Chart::read("swatch_grams=20\nCHART\n..\n".as_bytes()).unwrap();
+        let m = measure(&chart);
+
+        assert_eq!(None, m.width_cm);
+        assert_eq!(None, m.yarn[0].grams);
+    }
+}
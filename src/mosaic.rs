@@ -0,0 +1,104 @@
+use crate::chart::{Chart, Stitch};
+
+// Mosaic charts are knit with the two palette colors, each used for two rows at a time,
+// starting with the first color at the bottom. Each cell is the color that shows there. In
+// each row, the cells in that row's color are knit, and the others are slipped, pulling up the
+// stitch of the same color from the row below.
+
+// A cell that can't be made in a mosaic chart: it isn't in its row's color, and the cell
+// below it isn't the same color to be slipped.
+#[derive(Debug, PartialEq)]
+pub struct MosaicError {
+    pub row: usize,
+    pub col: usize,
+}
+
+// The palette index of the color the row is worked in.
+pub fn row_color(chart: &Chart, row: usize) -> usize {
+    let number = chart.rows() - row;
+    ((number - 1) / 2) % 2
+}
+
+pub fn check_mosaic(chart: &Chart) -> Vec<MosaicError> {
+    let mut errors = Vec::new();
+    for row in (0..chart.rows()).rev() {
+        for col in 0..chart.columns() {
+            let stitch = chart.stitch(row, col);
+            let knit = stitch == Stitch::Color(row_color(chart, row));
+            let slipped = match stitch {
+                Stitch::Color(_) => row + 1 < chart.rows() && chart.stitch(row + 1, col) == stitch,
+                _ => false,
+            };
+            if !knit && !slipped {
+                errors.push(MosaicError { row, col });
+            }
+        }
+    }
+    errors
+}
+
+// What is worked in the cell: a knit in the row's color, or a slipped stitch.
+pub(crate) fn worked_stitch(chart: &Chart, row: usize, col: usize) -> Stitch {
+    match chart.stitch(row, col) {
+        Stitch::Empty => Stitch::Empty,
+        stitch if stitch == Stitch::Color(row_color(chart, row)) => Stitch::Knit,
+        _ => Stitch::SlWyib,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::errors::*;
+
+    fn chart(rows: &str) -> Chart {
+        Chart::read(format!("mode=mosaic\npalette=A:black B:white\nCHART\n{}", rows).as_bytes())
+            .unwrap()
+    }
+
+    #[test]
+    fn row_colors() {
+        let c = chart("BBB\nBBB\nAAA\nAAA\nBBB\nBBB\nAAA\nAAA\n");
+
+        assert!(c.warnings().is_empty());
+        assert_eq!(
+            vec![0, 0, 1, 1, 0, 0, 1, 1],
+            (0..8)
+                .rev()
+                .map(|row| row_color(&c, row))
+                .collect::<Vec<_>>()
+        );
+        assert!(check_mosaic(&c).is_empty());
+    }
+
+    #[test]
+    fn slipped_cells() {
+        let c = chart("BAB\nBAB\nAAA\nAAA\n");
+        assert!(check_mosaic(&c).is_empty());
+        assert_eq!(Stitch::SlWyib, worked_stitch(&c, 1, 1));
+        assert_eq!(Stitch::Knit, worked_stitch(&c, 1, 0));
+
+        // A color can't be slipped up from the other one, and nothing can be slipped in the
+        // first row.
+        let c = chart("BAB\nBAB\nABA\nAAA\n");
+        assert_eq!(
+            vec![
+                MosaicError { row: 2, col: 1 },
+                MosaicError { row: 1, col: 1 }
+            ],
+            check_mosaic(&c)
+        );
+        let c = chart("AAB\n");
+        assert_eq!(vec![MosaicError { row: 0, col: 2 }], check_mosaic(&c));
+    }
+
+    #[test]
+    fn one_color() {
+        let c = Chart::read("mode=mosaic\npalette=A:black\nCHART\nA\nA\nA\n".as_bytes());
+        match c {
+            Err(Error(ErrorKind::BadAttrValue(name, _), _)) => assert_eq!("palette", name),
+            _ => panic!("a one color mosaic chart should be rejected"),
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use std::io::Write;
+
+use graphics::ellipse::Ellipse;
+use graphics_buffer::{RenderBuffer, IDENTITY};
+
+use crate::chart::Chart;
+use crate::errors::*;
+use crate::font::GLYPH_HEIGHT;
+use crate::machine::is_contrast;
+use crate::raster::{add_captions, draw_text, text_width, SYMBOL_COLOR};
+use crate::render::{write_png, RenderOptions, Renderer};
+
+// The stitches across a punchcard.
+pub const PUNCHCARD_WIDTH: usize = 24;
+
+// Cards shorter than this can't be joined into a loop that goes round the machine.
+pub const MIN_PUNCHCARD_ROWS: usize = 36;
+
+// The rows at each end of the card that overlap the other end when it is joined into a loop.
+pub const OVERLAP_ROWS: usize = 2;
+
+// The holes in a punchcard, as seen from the front of the machine. The machine knits the
+// card's pattern on the side of the fabric facing it, so the chart is reflected left to
+// right. The first row to be knit is at the top of the card. The holes are the contrast
+// stitches.
+#[derive(Debug, PartialEq)]
+pub struct Punchcard {
+    // From the top of the card down, with true for a hole. Each row is PUNCHCARD_WIDTH long.
+    pub rows: Vec<Vec<bool>>,
+}
+
+// Repeats the chart across the card, and up it until the card is long enough to go round
+// the machine.
+pub fn punchcard(chart: &Chart) -> Result<Punchcard> {
+    let (rows, cols) = (chart.rows(), chart.columns());
+    if cols == 0 || rows == 0 {
+        return Err(ErrorKind::BadPunchcardChart("The chart is empty.".into()).into());
+    }
+    if !PUNCHCARD_WIDTH.is_multiple_of(cols) {
+        return Err(ErrorKind::BadPunchcardChart(format!(
+            "It is {} stitches wide, which doesn't divide {}.",
+            cols, PUNCHCARD_WIDTH
+        ))
+        .into());
+    }
+    if chart.palette().len() > 2 {
+        return Err(ErrorKind::BadPunchcardChart("It has more than two colors.".into()).into());
+    }
+
+    let repeats = MIN_PUNCHCARD_ROWS.div_ceil(rows);
+    let card_rows = (0..repeats * rows)
+        .map(|card_row| {
+            let row = rows - 1 - card_row % rows;
+            (0..PUNCHCARD_WIDTH)
+                .map(|x| is_contrast(chart, chart.stitch(row, cols - 1 - x % cols)))
+                .collect()
+        })
+        .collect();
+    Ok(Punchcard { rows: card_rows })
+}
+
+// The size of a standard 24 stitch card, in mm.
+const CARD_WIDTH_MM: f64 = 142.0;
+const HOLE_PITCH_MM: f64 = 4.5;
+const ROW_PITCH_MM: f64 = 5.0;
+const HOLE_MM: f64 = 3.5;
+// The feed holes down each side, from the edges of the card.
+const FEED_HOLE_INSET_MM: f64 = 6.0;
+const MARGIN_MM: f64 = 10.0;
+
+// Templates are drawn at the chart's dpi, or this if it doesn't have one.
+const TEMPLATE_DPI: f64 = 150.0;
+
+const OVERLAP_COLOR: graphics::types::Color = [0.85, 0.85, 0.85, 1.0];
+const OUTLINE_COLOR: graphics::types::Color = [0.6, 0.6, 0.6, 1.0];
+
+// A printable, actual size template of the chart's punchcard, as a PNG. The rows that
+// overlap the other end of the card are shaded.
+pub struct PunchcardRenderer;
+
+impl Renderer for PunchcardRenderer {
+    fn extension(&self) -> &'static str {
+        "png"
+    }
+
+    fn render(&self, chart: &Chart, mut options: RenderOptions, w: &mut dyn Write) -> Result<()> {
+        let card = punchcard(chart)?;
+        let dpi = chart.dpi().unwrap_or(TEMPLATE_DPI);
+        let px = |mm: f64| mm * dpi / 25.4;
+
+        // The overlap rows at the top are the last rows of the card, and the ones at the
+        // bottom are the first rows.
+        let count = card.rows.len();
+        let template_rows: Vec<usize> = (count - OVERLAP_ROWS..count)
+            .chain(0..count)
+            .chain(0..OVERLAP_ROWS)
+            .collect();
+        let width = px(CARD_WIDTH_MM).round() as u32;
+        let height = px(2.0 * MARGIN_MM + template_rows.len() as f64 * ROW_PITCH_MM).round() as u32;
+        let mut buffer = RenderBuffer::new(width, height);
+        buffer.clear([1.0; 4]);
+
+        let left = px((CARD_WIDTH_MM - PUNCHCARD_WIDTH as f64 * HOLE_PITCH_MM) / 2.0);
+        let hole = px(HOLE_MM);
+        let scale = ((px(ROW_PITCH_MM) * 0.6 / f64::from(GLYPH_HEIGHT)) as u32).max(1);
+        for (index, card_row) in template_rows.iter().enumerate() {
+            let top = px(MARGIN_MM + index as f64 * ROW_PITCH_MM);
+            let center_y = top + px(ROW_PITCH_MM) / 2.0;
+            if index < OVERLAP_ROWS || index >= OVERLAP_ROWS + count {
+                let band = [0.0, top, f64::from(width), px(ROW_PITCH_MM)];
+                graphics::rectangle(OVERLAP_COLOR, band, IDENTITY, &mut buffer);
+            }
+
+            for x in &[
+                px(FEED_HOLE_INSET_MM),
+                px(CARD_WIDTH_MM - FEED_HOLE_INSET_MM),
+            ] {
+                let rectangle = [x - hole / 2.0, center_y - hole / 2.0, hole, hole];
+                Ellipse::new_border(OUTLINE_COLOR, 0.5).draw(
+                    rectangle,
+                    &Default::default(),
+                    IDENTITY,
+                    &mut buffer,
+                );
+            }
+            for (x, punched) in card.rows[*card_row].iter().enumerate() {
+                let center_x = left + (x as f64 + 0.5) * px(HOLE_PITCH_MM);
+                let rectangle = [center_x - hole / 2.0, center_y - hole / 2.0, hole, hole];
+                let ellipse = if *punched {
+                    Ellipse::new(SYMBOL_COLOR)
+                } else {
+                    Ellipse::new_border(OUTLINE_COLOR, 0.5)
+                };
+                ellipse.draw(rectangle, &Default::default(), IDENTITY, &mut buffer);
+            }
+
+            // Row numbers go between the feed holes and the pattern, on the left.
+            let label = (card_row + 1).to_string();
+            let label_x = left - px(1.0) - f64::from(text_width(&label, scale));
+            let label_y = center_y - f64::from(GLYPH_HEIGHT * scale) / 2.0;
+            draw_text(
+                &label,
+                label_x.max(0.0) as u32,
+                label_y.max(0.0) as u32,
+                scale,
+                &mut buffer,
+            );
+            if let Some(progress) = options.progress.as_mut() {
+                progress(index + 1, template_rows.len());
+            }
+        }
+
+        // The edges of the card.
+        let outline = graphics::line::Line::new(SYMBOL_COLOR, 0.5);
+        let (w_px, h_px) = (f64::from(width) - 1.0, f64::from(height) - 1.0);
+        for edge in &[
+            [0.0, 0.0, w_px, 0.0],
+            [0.0, h_px, w_px, h_px],
+            [0.0, 0.0, 0.0, h_px],
+            [w_px, 0.0, w_px, h_px],
+        ] {
+            outline.draw(*edge, &Default::default(), IDENTITY, &mut buffer);
+        }
+
+        let buffer = add_captions(chart, buffer);
+        write_png(chart, &buffer, Some(dpi), w)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tiled_and_flipped() {
+        let chart =
+            Chart::read("palette=A:white B:red\nCHART\nAAAAAB\nBAAAAA\n".as_bytes()).unwrap();
+        let card = punchcard(&chart).unwrap();
+
+        assert_eq!(MIN_PUNCHCARD_ROWS, card.rows.len());
+        assert!(card.rows.iter().all(|row| row.len() == PUNCHCARD_WIDTH));
+        // The bottom row of the chart comes first, reflected left to right.
+        assert_eq!(
+            vec![0, 6, 12, 18],
+            (0..PUNCHCARD_WIDTH)
+                .filter(|x| !card.rows[0][*x])
+                .step_by(5)
+                .collect::<Vec<_>>()
+        );
+        assert!(card.rows[0][5] && !card.rows[0][0]);
+        assert!(card.rows[1][0] && !card.rows[1][5]);
+        assert_eq!(card.rows[0], card.rows[2]);
+    }
+
+    #[test]
+    fn bad_charts() {
+        let too_wide = Chart::read("CHART\n.....\n".as_bytes()).unwrap();
+        assert!(punchcard(&too_wide).is_err());
+
+        let colors = Chart::read("palette=A:white B:red C:blue\nCHART\nABC\n".as_bytes()).unwrap();
+        assert!(punchcard(&colors).is_err());
+    }
+
+    #[test]
+    fn template() {
+        let chart = Chart::read("CHART\nX.\n".as_bytes()).unwrap();
+        let png = PunchcardRenderer
+            .render_to_bytes(&chart, RenderOptions::default())
+            .unwrap();
+        let image = image::load_from_memory(&png).unwrap().to_rgba();
+
+        // 142mm wide, and 36 rows and 4 overlap rows of 5mm, with 10mm margins, at 150 dpi.
+        assert_eq!((839, 1299), image.dimensions());
+    }
+}
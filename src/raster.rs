@@ -0,0 +1,785 @@
+use css_color_parser::Color as CssColor;
+use graphics::ellipse::Ellipse;
+use graphics::line::Line;
+use graphics::types::Matrix2d;
+use graphics::Transformed;
+use graphics_buffer::{RenderBuffer, IDENTITY};
+use image::GenericImage;
+
+use crate::attributes::Mode;
+use crate::chart::{Chart, Stitch};
+use crate::custom::{arc, Shape};
+use crate::double_knitting::back_stitch;
+use crate::font::{glyph, glyph_pixel, GLYPH_HEIGHT, GLYPH_WIDTH};
+use crate::intarsia::find_intarsia;
+
+// Called as the stitches are drawn, with the number of rows done and the total.
+pub type Progress<'a> = &'a mut dyn FnMut(usize, usize);
+
+// Draws the chart as an image. Cells in `highlights` are given a wash of the chart's
+// highlight color.
+pub fn rasterize(
+    chart: &Chart,
+    highlights: &[(usize, usize)],
+    mut progress: Option<Progress>,
+) -> RenderBuffer {
+    let rows = chart.rows();
+    let front = |row, col| chart.stitch(row, col);
+    if chart.mode() != Mode::DoubleKnitting {
+        let mut buffer = draw_cells(chart, &front, highlights, &mut |done| {
+            if let Some(progress) = progress.as_mut() {
+                progress(done, rows);
+            }
+        });
+        if chart.bobbin_labels() {
+            draw_bobbin_labels(chart, &mut buffer);
+        }
+        return add_captions(chart, buffer);
+    }
+
+    // Double knitting shows the back to the right of the front, a cell's width away.
+    let mut front_buffer = draw_cells(chart, &front, highlights, &mut |done| {
+        if let Some(progress) = progress.as_mut() {
+            progress(done, 2 * rows);
+        }
+    });
+    if chart.bobbin_labels() {
+        draw_bobbin_labels(chart, &mut front_buffer);
+    }
+    let back = |row, col| back_stitch(chart, row, col);
+    let back_highlights: Vec<_> = highlights
+        .iter()
+        .map(|(row, col)| (*row, chart.columns() - 1 - col))
+        .collect();
+    let back_buffer = draw_cells(chart, &back, &back_highlights, &mut |done| {
+        if let Some(progress) = progress.as_mut() {
+            progress(rows + done, 2 * rows);
+        }
+    });
+
+    let gap = chart.cell_width().round() as u32;
+    let mut buffer = RenderBuffer::new(2 * front_buffer.width() + gap, front_buffer.height());
+    buffer.clear(to_color_array(chart.background_color()));
+    buffer.copy_from(&*front_buffer, 0, 0);
+    buffer.copy_from(&*back_buffer, front_buffer.width() + gap, 0);
+    add_captions(chart, buffer)
+}
+
+// Draws the cells of the chart, with `stitch` giving the stitch in each (row, col). Calls
+// `progress` with the number of rows drawn.
+fn draw_cells(
+    chart: &Chart,
+    stitch: &dyn Fn(usize, usize) -> Stitch,
+    highlights: &[(usize, usize)],
+    progress: &mut dyn FnMut(usize),
+) -> RenderBuffer {
+    // Only the stitch symbols are drawn at the chart's quality, in their stamps. Everything
+    // else is straight edged.
+    let (width, height) = chart.pixel_size();
+    let mut buffer = RenderBuffer::new(width, height);
+
+    buffer.clear(to_color_array(chart.background_color()));
+
+    // Colorwork cells are filled first so everything else is drawn over them. Runs of a
+    // color in a row are filled together.
+    let palette: Vec<_> = chart
+        .palette()
+        .iter()
+        .map(|(_, color)| to_color_array(*color))
+        .collect();
+    for row in 0..chart.rows() {
+        let mut col = 0;
+        while col < chart.columns() {
+            let cell = stitch(row, col);
+            let run = (col..chart.columns())
+                .take_while(|c| stitch(row, *c) == cell)
+                .count();
+            if let Stitch::Color(index) = cell {
+                let [x, y, _, height] = cell_rectangle(chart, row, col);
+                let rectangle = [x, y, run as f64 * chart.cell_width(), height];
+                graphics::rectangle(palette[index], rectangle, IDENTITY, &mut buffer);
+            }
+            col += run;
+        }
+    }
+
+    let mut highlight_color = to_color_array(chart.highlight_color());
+    highlight_color[3] *= 0.5;
+    for (row, col) in highlights {
+        let rectangle = cell_rectangle(chart, *row, *col);
+        graphics::rectangle(highlight_color, rectangle, IDENTITY, &mut buffer);
+    }
+
+    // The fabric preview has no grid.
+    if !chart.true_proportions() {
+        draw_grid(chart, IDENTITY, &mut buffer);
+    }
+
+    let mut stamps: Vec<(Stitch, Option<RenderBuffer>)> = Vec::new();
+    for row in 0..chart.rows() {
+        for col in 0..chart.columns() {
+            let cell = stitch(row, col);
+            let index = match stamps.iter().position(|(s, _)| *s == cell) {
+                Some(index) => index,
+                None => {
+                    stamps.push((cell, make_stamp(chart, cell, chart.quality())));
+                    stamps.len() - 1
+                }
+            };
+            if let Some(stamp) = &stamps[index].1 {
+                let [x, y, _, _] = cell_rectangle(chart, row, col);
+                let left = x.round() as i64 - STAMP_MARGIN as i64;
+                let top = y.round() as i64 - STAMP_MARGIN as i64;
+                apply_stamp(stamp, left, top, &mut buffer);
+            }
+        }
+        progress(row + 1);
+    }
+    buffer
+}
+
+// Room around the cell in a stamp, for lines that stick out of it a little.
+const STAMP_MARGIN: u32 = 2;
+
+// Each kind of stitch is drawn once, into a stamp that is copied into every cell with that
+// stitch. That is much faster than drawing the symbol in every cell. Returns None for
+// stitches that don't draw anything. The stamp is drawn `quality` times bigger and scaled
+// down.
+fn make_stamp(chart: &Chart, stitch: Stitch, quality: u32) -> Option<RenderBuffer> {
+    let width = chart.cell_width().ceil() as u32 + 2 * STAMP_MARGIN;
+    let height = chart.cell_height().ceil() as u32 + 2 * STAMP_MARGIN;
+    let mut stamp = RenderBuffer::new(width * quality, height * quality);
+    stamp.clear([0.0; 4]);
+
+    let scale = f64::from(quality);
+    let margin = f64::from(STAMP_MARGIN) * scale;
+    let transform = IDENTITY.trans(margin, margin).scale(scale, scale);
+    let cell = [0.0, 0.0, chart.cell_width(), chart.cell_height()];
+    if chart.true_proportions() {
+        draw_fabric_stitch(chart, stitch, cell, transform, &mut stamp);
+    } else {
+        draw_stitch(chart, stitch, cell, transform, &mut stamp);
+    }
+
+    let pixels: &[u8] = &stamp;
+    if !pixels.chunks(4).any(|pixel| pixel[3] > 0) {
+        None
+    } else if quality > 1 {
+        Some(downsample(&stamp, quality))
+    } else {
+        Some(stamp)
+    }
+}
+
+// Blends the stamp over the buffer with its top left corner at (left, top).
+fn apply_stamp(stamp: &RenderBuffer, left: i64, top: i64, buffer: &mut RenderBuffer) {
+    let (stamp_width, stamp_height) = (i64::from(stamp.width()), i64::from(stamp.height()));
+    let (width, height) = (i64::from(buffer.width()), i64::from(buffer.height()));
+    let from: &[u8] = stamp;
+    let to: &mut [u8] = buffer;
+
+    for sy in 0..stamp_height {
+        let y = top + sy;
+        if y < 0 || y >= height {
+            continue;
+        }
+        for sx in 0..stamp_width {
+            let x = left + sx;
+            if x < 0 || x >= width {
+                continue;
+            }
+            let s = ((sy * stamp_width + sx) * 4) as usize;
+            let alpha = u32::from(from[s + 3]);
+            if alpha == 0 {
+                continue;
+            }
+            let d = ((y * width + x) * 4) as usize;
+            for channel in 0..3 {
+                let blended = u32::from(from[s + channel]) * alpha
+                    + u32::from(to[d + channel]) * (255 - alpha);
+                to[d + channel] = ((blended + 127) / 255) as u8;
+            }
+            let dest_alpha = u32::from(to[d + 3]);
+            to[d + 3] = (alpha + (dest_alpha * (255 - alpha) + 127) / 255) as u8;
+        }
+    }
+}
+
+// Averages each factor x factor block of pixels into one. Colors are weighted by their alpha,
+// so transparent pixels don't darken the edges of a stamp.
+fn downsample(buffer: &RenderBuffer, factor: u32) -> RenderBuffer {
+    let width = buffer.width() / factor;
+    let height = buffer.height() / factor;
+    let big_width = buffer.width() as usize;
+    let factor = factor as usize;
+    let samples = (factor * factor) as u32;
+
+    let mut small = RenderBuffer::new(width, height);
+    let from: &[u8] = buffer;
+    let to: &mut [u8] = &mut small;
+    for y in 0..height as usize {
+        for x in 0..width as usize {
+            let mut sum = [0u32; 4];
+            for sy in 0..factor {
+                let start = ((y * factor + sy) * big_width + x * factor) * 4;
+                for pixel in from[start..start + factor * 4].chunks(4) {
+                    let alpha = u32::from(pixel[3]);
+                    for channel in 0..3 {
+                        sum[channel] += u32::from(pixel[channel]) * alpha;
+                    }
+                    sum[3] += alpha;
+                }
+            }
+            let d = (y * width as usize + x) * 4;
+            // A transparent block has no color, and comes out black.
+            let weight = sum[3].max(1);
+            for channel in 0..3 {
+                to[d + channel] = ((sum[channel] + weight / 2) / weight) as u8;
+            }
+            to[d + 3] = ((sum[3] + samples / 2) / samples) as u8;
+        }
+    }
+    small
+}
+
+// Captions are wrapped to the width of the chart, but no narrower than this.
+const MIN_CAPTION_CHARS: u32 = 30;
+
+// Pixels per font pixel for captions. The title is twice as big.
+fn caption_scale(chart: &Chart) -> u32 {
+    ((chart.cell_width() / 10.0).round() as u32).max(1)
+}
+
+fn line_height(scale: u32) -> u32 {
+    (GLYPH_HEIGHT + 3) * scale
+}
+
+pub(crate) fn text_width(text: &str, scale: u32) -> u32 {
+    text.chars().count() as u32 * (GLYPH_WIDTH + 1) * scale
+}
+
+// Breaks text into lines of at most `chars` chars, at spaces. Words longer than that get a
+// line to themselves.
+fn wrap(text: &str, chars: u32) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && (line.chars().count() + 1 + word.chars().count()) as u32 > chars {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+pub(crate) fn draw_text(text: &str, x: u32, y: u32, scale: u32, buffer: &mut RenderBuffer) {
+    for (index, ch) in text.chars().enumerate() {
+        let left = x + index as u32 * (GLYPH_WIDTH + 1) * scale;
+        let glyph = glyph(ch);
+        for gy in 0..GLYPH_HEIGHT {
+            for gx in 0..GLYPH_WIDTH {
+                if glyph_pixel(glyph, gx, gy) {
+                    let rectangle = [
+                        f64::from(left + gx * scale),
+                        f64::from(y + gy * scale),
+                        f64::from(scale),
+                        f64::from(scale),
+                    ];
+                    graphics::rectangle(SYMBOL_COLOR, rectangle, IDENTITY, buffer);
+                }
+            }
+        }
+    }
+}
+
+// Numbers each bobbin, counting from 1, in the first cell it knits. The number is drawn on
+// the background color so it shows up on any color.
+fn draw_bobbin_labels(chart: &Chart, buffer: &mut RenderBuffer) {
+    let scale = ((chart.cell_height() * 0.6 / f64::from(GLYPH_HEIGHT)) as u32).max(1);
+    let background = to_color_array(chart.background_color());
+    let intarsia = find_intarsia(chart);
+    for bobbin in 0..intarsia.bobbins.len() {
+        // unwrap: every bobbin knits at least one block.
+        let block = intarsia.blocks.iter().find(|b| b.bobbin == bobbin).unwrap();
+        let label = (bobbin + 1).to_string();
+        let width = text_width(&label, scale) - scale;
+        let height = GLYPH_HEIGHT * scale;
+        let [x, y, cell_width, cell_height] = cell_rectangle(chart, block.row, block.end_col);
+        let left = (x + (cell_width - f64::from(width)) / 2.0).max(0.0) as u32;
+        let top = (y + (cell_height - f64::from(height)) / 2.0).max(0.0) as u32;
+        let pad = f64::from(scale);
+        let rectangle = [
+            f64::from(left) - pad,
+            f64::from(top) - pad,
+            f64::from(width) + 2.0 * pad,
+            f64::from(height) + 2.0 * pad,
+        ];
+        graphics::rectangle(background, rectangle, IDENTITY, buffer);
+        draw_text(&label, left, top, scale, buffer);
+    }
+}
+
+// Puts the title above the chart, and the designer, size, notes and copyright below it.
+pub(crate) fn add_captions(chart: &Chart, buffer: RenderBuffer) -> RenderBuffer {
+    let scale = caption_scale(chart);
+    let chars = (buffer.width() / ((GLYPH_WIDTH + 1) * scale)).max(MIN_CAPTION_CHARS);
+
+    let above: Vec<String> = wrap(chart.title(), chars / 2);
+    let mut below: Vec<String> = Vec::new();
+    if !chart.designer().is_empty() {
+        below.extend(wrap(&format!("Designer: {}", chart.designer()), chars));
+    }
+    if !chart.size().is_empty() {
+        below.extend(wrap(&format!("Size: {}", chart.size()), chars));
+    }
+    below.extend(wrap(chart.notes(), chars));
+    // The font only has ASCII.
+    below.extend(wrap(&chart.copyright().replace('©', "(c)"), chars));
+    if above.is_empty() && below.is_empty() {
+        return buffer;
+    }
+
+    let pad = line_height(scale) / 2;
+    let above_height = above.len() as u32 * line_height(scale * 2);
+    let below_height = below.len() as u32 * line_height(scale);
+    let text_width = above
+        .iter()
+        .map(|line| text_width(line, scale * 2))
+        .chain(below.iter().map(|line| text_width(line, scale)))
+        .max()
+        .unwrap_or(0);
+    let width = buffer.width().max(text_width + 2 * pad);
+    let height = buffer.height() + above_height + below_height + 2 * pad;
+
+    let mut captioned = RenderBuffer::new(width, height);
+    captioned.clear(to_color_array(chart.background_color()));
+    let chart_top = pad + above_height;
+    captioned.copy_from(&*buffer, (width - buffer.width()) / 2, chart_top);
+
+    for (index, line) in above.iter().enumerate() {
+        let y = pad / 2 + index as u32 * line_height(scale * 2);
+        draw_text(line, pad, y, scale * 2, &mut captioned);
+    }
+    for (index, line) in below.iter().enumerate() {
+        let y = chart_top + buffer.height() + pad + index as u32 * line_height(scale);
+        draw_text(line, pad, y, scale, &mut captioned);
+    }
+    captioned
+}
+
+// [x, y, width, height] of the cell in the image.
+fn cell_rectangle(chart: &Chart, row: usize, col: usize) -> [f64; 4] {
+    let width = chart.cell_width();
+    let height = chart.cell_height();
+    [col as f64 * width, row as f64 * height, width, height]
+}
+
+fn draw_grid(chart: &Chart, transform: Matrix2d, buffer: &mut RenderBuffer) {
+    let line = Line::new(to_color_array(chart.grid_color()), 1.0);
+    let width = chart.columns() as f64 * chart.cell_width();
+    let height = chart.rows() as f64 * chart.cell_height();
+
+    for col in 0..=chart.columns() {
+        let x = col as f64 * chart.cell_width();
+        line.draw([x, 0.0, x, height], &Default::default(), transform, buffer);
+    }
+    for row in 0..=chart.rows() {
+        let y = row as f64 * chart.cell_height();
+        line.draw([0.0, y, width, y], &Default::default(), transform, buffer);
+    }
+
+    // Major lines are counted from the bottom right, where knitting starts. Only the lines
+    // inside the chart are drawn heavier.
+    if let Some(major) = chart.major_column_lines() {
+        let line = Line::new(to_color_array(major.color), major.width / 2.0);
+        for count in (major.interval..chart.columns()).step_by(major.interval) {
+            let x = (chart.columns() - count) as f64 * chart.cell_width();
+            line.draw([x, 0.0, x, height], &Default::default(), transform, buffer);
+        }
+    }
+    if let Some(major) = chart.major_row_lines() {
+        let line = Line::new(to_color_array(major.color), major.width / 2.0);
+        for count in (major.interval..chart.rows()).step_by(major.interval) {
+            let y = (chart.rows() - count) as f64 * chart.cell_height();
+            line.draw([0.0, y, width, y], &Default::default(), transform, buffer);
+        }
+    }
+}
+
+pub(crate) const SYMBOL_COLOR: graphics::types::Color = [0.1, 0.1, 0.1, 1.0];
+
+// Draws a line between two points given as fractions of the cell's width and height.
+fn stroke(
+    line: &Line,
+    cell: [f64; 4],
+    from: [f64; 2],
+    to: [f64; 2],
+    transform: Matrix2d,
+    buffer: &mut RenderBuffer,
+) {
+    let [x, y, width, height] = cell;
+    line.draw_from_to(
+        [x + from[0] * width, y + from[1] * height],
+        [x + to[0] * width, y + to[1] * height],
+        &Default::default(),
+        transform,
+        buffer,
+    );
+}
+
+// A dot of the given diameter, centered at a point given as fractions of the cell.
+fn dot(
+    center: [f64; 2],
+    diameter: f64,
+    cell: [f64; 4],
+    transform: Matrix2d,
+    buffer: &mut RenderBuffer,
+) {
+    let [x, y, width, height] = cell;
+    let rectangle = [
+        x + center[0] * width - diameter / 2.0,
+        y + center[1] * height - diameter / 2.0,
+        diameter,
+        diameter,
+    ];
+    graphics::ellipse(SYMBOL_COLOR, rectangle, transform, buffer);
+}
+
+fn draw_stitch(
+    chart: &Chart,
+    stitch: Stitch,
+    cell: [f64; 4],
+    transform: Matrix2d,
+    buffer: &mut RenderBuffer,
+) {
+    let [x, y, width, height] = cell;
+    let size = width.min(height);
+    let line = Line::new(SYMBOL_COLOR, (size / 20.0).max(0.5));
+
+    match stitch {
+        Stitch::Knit | Stitch::Empty | Stitch::Color(_) => {}
+        Stitch::Purl => dot([0.5, 0.5], chart.dot_size(), cell, transform, buffer),
+        Stitch::Yo => {
+            let diameter = size * 0.6;
+            let rectangle = [
+                x + (width - diameter) / 2.0,
+                y + (height - diameter) / 2.0,
+                diameter,
+                diameter,
+            ];
+            Ellipse::new_border(SYMBOL_COLOR, line.radius).draw(
+                rectangle,
+                &Default::default(),
+                transform,
+                buffer,
+            );
+        }
+        Stitch::M1L => {
+            stroke(&line, cell, [0.5, 0.8], [0.5, 0.2], transform, buffer);
+            stroke(&line, cell, [0.5, 0.2], [0.25, 0.45], transform, buffer);
+        }
+        Stitch::M1R => {
+            stroke(&line, cell, [0.5, 0.8], [0.5, 0.2], transform, buffer);
+            stroke(&line, cell, [0.5, 0.2], [0.75, 0.45], transform, buffer);
+        }
+        Stitch::K2tog => stroke(&line, cell, [0.2, 0.8], [0.8, 0.2], transform, buffer),
+        Stitch::Ssk => stroke(&line, cell, [0.2, 0.2], [0.8, 0.8], transform, buffer),
+        Stitch::P2tog => {
+            stroke(&line, cell, [0.2, 0.8], [0.8, 0.2], transform, buffer);
+            dot([0.3, 0.3], size * 0.2, cell, transform, buffer);
+        }
+        Stitch::K3tog => {
+            stroke(&line, cell, [0.2, 0.8], [0.8, 0.2], transform, buffer);
+            stroke(&line, cell, [0.5, 0.5], [0.5, 0.2], transform, buffer);
+        }
+        Stitch::Sssk => {
+            stroke(&line, cell, [0.2, 0.2], [0.8, 0.8], transform, buffer);
+            stroke(&line, cell, [0.5, 0.5], [0.5, 0.2], transform, buffer);
+        }
+        Stitch::Cdd => {
+            stroke(&line, cell, [0.2, 0.8], [0.5, 0.2], transform, buffer);
+            stroke(&line, cell, [0.5, 0.2], [0.8, 0.8], transform, buffer);
+            stroke(&line, cell, [0.5, 0.2], [0.5, 0.8], transform, buffer);
+        }
+        Stitch::Ktbl | Stitch::Ptbl => {
+            // Crossed legs under a loop.
+            stroke(&line, cell, [0.35, 0.85], [0.65, 0.35], transform, buffer);
+            stroke(&line, cell, [0.65, 0.85], [0.35, 0.35], transform, buffer);
+            let loop_top = arc([0.5, 0.35], 0.15, 180.0, 360.0);
+            draw_shape(&line, &loop_top, cell, transform, buffer);
+            if stitch == Stitch::Ptbl {
+                dot([0.2, 0.8], size * 0.2, cell, transform, buffer);
+            }
+        }
+        Stitch::SlWyib | Stitch::SlWyif => {
+            stroke(&line, cell, [0.25, 0.2], [0.5, 0.8], transform, buffer);
+            stroke(&line, cell, [0.5, 0.8], [0.75, 0.2], transform, buffer);
+            // The yarn across the front.
+            if stitch == Stitch::SlWyif {
+                stroke(&line, cell, [0.15, 0.5], [0.85, 0.5], transform, buffer);
+            }
+        }
+        Stitch::Bobble => {
+            let outline = Shape::Circle {
+                center: [0.5, 0.5],
+                radius: 0.35,
+            };
+            draw_shape(&line, &outline, cell, transform, buffer);
+            dot([0.5, 0.5], size * 0.3, cell, transform, buffer);
+        }
+        Stitch::Nupp => {
+            let rectangle = [
+                x + width * 0.15,
+                y + height * 0.35,
+                width * 0.7,
+                height * 0.3,
+            ];
+            graphics::ellipse(SYMBOL_COLOR, rectangle, transform, buffer);
+        }
+        Stitch::WrapTurn => {
+            // An arrow turning back.
+            let turn = arc([0.5, 0.6], 0.25, 180.0, 360.0);
+            draw_shape(&line, &turn, cell, transform, buffer);
+            stroke(&line, cell, [0.25, 0.6], [0.12, 0.45], transform, buffer);
+            stroke(&line, cell, [0.25, 0.6], [0.38, 0.45], transform, buffer);
+        }
+        // Brioche knits are arches and brioche purls are the arches upside down. Brioche
+        // decreases and increases add the lines of the plain ones under an arch.
+        Stitch::Brk => {
+            let arch = arc([0.5, 0.75], 0.3, 180.0, 360.0);
+            draw_shape(&line, &arch, cell, transform, buffer);
+        }
+        Stitch::Brp => {
+            let arch = arc([0.5, 0.25], 0.3, 0.0, 180.0);
+            draw_shape(&line, &arch, cell, transform, buffer);
+        }
+        Stitch::Sl1yo => {
+            stroke(&line, cell, [0.3, 0.35], [0.5, 0.8], transform, buffer);
+            stroke(&line, cell, [0.5, 0.8], [0.7, 0.35], transform, buffer);
+            stroke(&line, cell, [0.2, 0.2], [0.8, 0.2], transform, buffer);
+        }
+        Stitch::Brk2tog | Stitch::Brssk | Stitch::Brk3tog | Stitch::BrkYoBrk => {
+            let arch = arc([0.5, 0.4], 0.2, 180.0, 360.0);
+            draw_shape(&line, &arch, cell, transform, buffer);
+            match stitch {
+                Stitch::Brk2tog => {
+                    stroke(&line, cell, [0.2, 0.85], [0.8, 0.45], transform, buffer);
+                }
+                Stitch::Brssk => {
+                    stroke(&line, cell, [0.2, 0.45], [0.8, 0.85], transform, buffer);
+                }
+                Stitch::Brk3tog => {
+                    stroke(&line, cell, [0.2, 0.85], [0.8, 0.45], transform, buffer);
+                    stroke(&line, cell, [0.5, 0.65], [0.5, 0.45], transform, buffer);
+                }
+                _ => {
+                    stroke(&line, cell, [0.5, 0.85], [0.5, 0.2], transform, buffer);
+                    stroke(&line, cell, [0.3, 0.85], [0.5, 0.6], transform, buffer);
+                    stroke(&line, cell, [0.7, 0.85], [0.5, 0.6], transform, buffer);
+                }
+            }
+        }
+        Stitch::Custom(index) => {
+            for shape in &chart.custom_stitches()[index].symbol {
+                draw_shape(&line, shape, cell, transform, buffer);
+            }
+        }
+    }
+}
+
+// Draws part of a custom stitch's symbol.
+fn draw_shape(
+    line: &Line,
+    shape: &Shape,
+    cell: [f64; 4],
+    transform: Matrix2d,
+    buffer: &mut RenderBuffer,
+) {
+    let [x, y, width, height] = cell;
+    match shape {
+        Shape::Circle { center, radius } | Shape::Dot { center, radius } => {
+            let rectangle = [
+                x + (center[0] - radius) * width,
+                y + (center[1] - radius) * height,
+                2.0 * radius * width,
+                2.0 * radius * height,
+            ];
+            let ellipse = if let Shape::Dot { .. } = shape {
+                Ellipse::new(SYMBOL_COLOR)
+            } else {
+                Ellipse::new_border(SYMBOL_COLOR, line.radius)
+            };
+            ellipse.draw(rectangle, &Default::default(), transform, buffer);
+        }
+        Shape::Polyline(points) => {
+            for pair in points.windows(2) {
+                stroke(line, cell, pair[0], pair[1], transform, buffer);
+            }
+        }
+    }
+}
+
+// A preview of the knitted fabric: knits drawn as Vs and purls as bumps, in cells shaped
+// like the stitches.
+fn draw_fabric_stitch(
+    chart: &Chart,
+    stitch: Stitch,
+    cell: [f64; 4],
+    transform: Matrix2d,
+    buffer: &mut RenderBuffer,
+) {
+    let line = Line::new(to_color_array(chart.grid_color()), 1.0);
+    let [x, y, width, height] = cell;
+    match stitch {
+        Stitch::Empty => {}
+        Stitch::Purl | Stitch::P2tog | Stitch::Ptbl => {
+            let rectangle = [x + width * 0.1, y + height * 0.3, width * 0.8, height * 0.4];
+            Ellipse::new_border(line.color, 1.0).draw(
+                rectangle,
+                &Default::default(),
+                transform,
+                buffer,
+            );
+        }
+        Stitch::Yo => {
+            let rectangle = [x + width * 0.3, y + height * 0.3, width * 0.4, height * 0.4];
+            Ellipse::new_border(line.color, 1.0).draw(
+                rectangle,
+                &Default::default(),
+                transform,
+                buffer,
+            );
+        }
+        _ => {
+            stroke(&line, cell, [0.1, 0.1], [0.5, 0.9], transform, buffer);
+            stroke(&line, cell, [0.9, 0.1], [0.5, 0.9], transform, buffer);
+        }
+    }
+}
+
+fn to_color_array(color: CssColor) -> graphics::types::Color {
+    use graphics::types::ColorComponent;
+
+    [
+        ColorComponent::from(color.r) / 255.0,
+        ColorComponent::from(color.g) / 255.0,
+        ColorComponent::from(color.b) / 255.0,
+        color.a,
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn pixel(buffer: &RenderBuffer, x: u32, y: u32) -> [u8; 4] {
+        buffer.get_pixel(x, y).0
+    }
+
+    #[test]
+    fn size_and_fills() {
+        let chart = Chart::read(
+            "cell_size=10\nbackground_color=white\npalette=A:red\nCHART\nAA.\n".as_bytes(),
+        )
+        .unwrap();
+        let buffer = rasterize(&chart, &[], None);
+
+        assert_eq!((30, 10), (buffer.width(), buffer.height()));
+        assert_eq!([255, 0, 0, 255], pixel(&buffer, 15, 5));
+        assert_eq!([255, 255, 255, 255], pixel(&buffer, 25, 5));
+    }
+
+    #[test]
+    fn stamps_match_cells() {
+        // Every purl gets the same dot, centered in its cell.
+        let chart = Chart::read(
+            "cell_size=10\ndot_size=6\nbackground_color=white\nCHART\nX.X\n".as_bytes(),
+        )
+        .unwrap();
+        let buffer = rasterize(&chart, &[], None);
+
+        assert_ne!([255, 255, 255, 255], pixel(&buffer, 5, 5));
+        assert_eq!([255, 255, 255, 255], pixel(&buffer, 15, 5));
+        assert_eq!(pixel(&buffer, 5, 5), pixel(&buffer, 25, 5));
+    }
+
+    #[test]
+    fn custom_symbols() {
+        let chart = Chart::read(
+            "stitch_bobble=B | bobble | mb | dot 0.5 0.5 0.3\nstitch_tbl=T | twisted | k1tbl | path M 0 0.5 H 1\nCHART\nBT.\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        let buffer = rasterize(&chart, &[], None);
+
+        let background = pixel(&buffer, 37, 7);
+        assert_ne!(background, pixel(&buffer, 7, 7));
+        assert_ne!(background, pixel(&buffer, 22, 7));
+        assert_eq!(background, pixel(&buffer, 22, 3));
+    }
+
+    #[test]
+    fn progress_by_row() {
+        let chart = Chart::read("CHART\n..\n..\n..\n".as_bytes()).unwrap();
+        let mut seen = Vec::new();
+        let mut progress = |done, total| seen.push((done, total));
+        rasterize(&chart, &[], Some(&mut progress));
+
+        assert_eq!(vec![(1, 3), (2, 3), (3, 3)], seen);
+    }
+
+    #[test]
+    fn double_knitting_back() {
+        let chart = Chart::read(
+            "mode=double_knitting\ncell_size=10\npalette=A:black B:white\nCHART\nAB\n".as_bytes(),
+        )
+        .unwrap();
+        let mut seen = Vec::new();
+        let mut progress = |done, total| seen.push((done, total));
+        let buffer = rasterize(&chart, &[], Some(&mut progress));
+
+        // The back is reflected, in the other colors.
+        assert_eq!((50, 10), (buffer.width(), buffer.height()));
+        assert_eq!([0, 0, 0, 255], pixel(&buffer, 5, 5));
+        assert_eq!([255, 255, 255, 255], pixel(&buffer, 15, 5));
+        assert_eq!([0, 0, 0, 255], pixel(&buffer, 35, 5));
+        assert_eq!([255, 255, 255, 255], pixel(&buffer, 45, 5));
+        assert_eq!(vec![(1, 2), (2, 2)], seen);
+    }
+
+    #[test]
+    fn bobbin_labels() {
+        let chart = Chart::read(
+            "cell_size=20\nbobbin_labels=true\nbackground_color=white\npalette=A:red B:blue\nCHART\nAB\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        let buffer = rasterize(&chart, &[], None);
+
+        // The label box covers the middle of the cell, and leaves its corners.
+        let labeled = (0..20)
+            .flat_map(|y| (20..40).map(move |x| (x, y)))
+            .filter(|(x, y)| pixel(&buffer, *x, *y) == [255, 255, 255, 255])
+            .count();
+        assert!(labeled > 0);
+        assert_eq!([0, 0, 255, 255], pixel(&buffer, 22, 2));
+    }
+
+    #[test]
+    fn downsample_averages() {
+        let mut buffer = RenderBuffer::new(4, 2);
+        buffer.clear([1.0, 1.0, 1.0, 1.0]);
+        buffer.put_pixel(0, 0, image::Rgba([0, 0, 0, 255]));
+        let small = downsample(&buffer, 2);
+
+        assert_eq!((2, 1), (small.width(), small.height()));
+        assert_eq!([191, 191, 191, 255], pixel(&small, 0, 0));
+        assert_eq!([255, 255, 255, 255], pixel(&small, 1, 0));
+    }
+}
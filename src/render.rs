@@ -0,0 +1,149 @@
+use std::io::Write;
+
+use graphics_buffer::RenderBuffer;
+
+use crate::chart::Chart;
+use crate::errors::*;
+use crate::raster::{rasterize, Progress};
+
+#[derive(Default)]
+pub struct RenderOptions<'a> {
+    // Cells to give a wash of the chart's highlight color, e.g. long floats.
+    pub highlights: &'a [(usize, usize)],
+    pub progress: Option<Progress<'a>>,
+}
+
+// Draws charts in some image format.
+pub trait Renderer {
+    // The usual file extension for the format, without the dot.
+    fn extension(&self) -> &'static str;
+
+    fn render(&self, chart: &Chart, options: RenderOptions, w: &mut dyn Write) -> Result<()>;
+
+    fn render_to_bytes(&self, chart: &Chart, options: RenderOptions) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.render(chart, options, &mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+// 8-bit RGBA PNGs, with the chart's captions and dpi in the metadata.
+pub struct PngRenderer;
+
+impl Renderer for PngRenderer {
+    fn extension(&self) -> &'static str {
+        "png"
+    }
+
+    fn render(&self, chart: &Chart, options: RenderOptions, w: &mut dyn Write) -> Result<()> {
+        let buffer = rasterize(chart, options.highlights, options.progress);
+        write_png(chart, &buffer, chart.dpi(), w)
+    }
+}
+
+// The chart's captions, with the PNG keywords they are saved under.
+fn png_text(chart: &Chart) -> Vec<(&'static str, &str)> {
+    vec![
+        ("Title", chart.title()),
+        ("Author", chart.designer()),
+        ("Copyright", chart.copyright()),
+        ("Description", chart.notes()),
+        ("Size", chart.size()),
+    ]
+    .into_iter()
+    .filter(|(_, text)| !text.is_empty())
+    .collect()
+}
+
+// tEXt chunks can only hold Latin-1, so anything else goes in an iTXt chunk as UTF-8.
+fn write_text_chunk(
+    writer: &mut png::Writer<&mut dyn Write>,
+    keyword: &str,
+    text: &str,
+) -> Result<()> {
+    let mut data = keyword.as_bytes().to_vec();
+    data.push(0);
+    if text.chars().all(|ch| (ch as u32) < 0x100) {
+        data.extend(text.chars().map(|ch| ch as u8));
+        writer.write_chunk(*b"tEXt", &data)?;
+    } else {
+        // No compression, and empty language tag and translated keyword.
+        data.extend(&[0, 0, 0, 0]);
+        data.extend(text.as_bytes());
+        writer.write_chunk(*b"iTXt", &data)?;
+    }
+    Ok(())
+}
+
+// Writes the buffer with the chart's captions, and with the dpi if there is one.
+pub(crate) fn write_png(
+    chart: &Chart,
+    buffer: &RenderBuffer,
+    dpi: Option<f64>,
+    w: &mut dyn Write,
+) -> Result<()> {
+    let mut encoder = png::Encoder::new(w, buffer.width(), buffer.height());
+    encoder.set_color(png::ColorType::RGBA);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    if let Some(dpi) = dpi {
+        // Pixels per meter, in both directions.
+        let ppm = (dpi / 0.0254).round() as u32;
+        let mut data = Vec::new();
+        data.extend(&ppm.to_be_bytes());
+        data.extend(&ppm.to_be_bytes());
+        data.push(1);
+        writer.write_chunk(png::chunk::pHYs, &data)?;
+    }
+    for (keyword, text) in png_text(chart) {
+        write_text_chunk(&mut writer, keyword, text)?;
+    }
+    let data: &[u8] = buffer;
+    Ok(writer.write_image_data(data)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // The (type, data) of each chunk in a PNG.
+    fn chunks(png: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let mut chunks = Vec::new();
+        let mut i = 8;
+        while i + 8 <= png.len() {
+            let length = u32::from_be_bytes([png[i], png[i + 1], png[i + 2], png[i + 3]]) as usize;
+            let name = String::from_utf8_lossy(&png[i + 4..i + 8]).into_owned();
+            chunks.push((name, png[i + 8..i + 8 + length].to_vec()));
+            i += 12 + length;
+        }
+        chunks
+    }
+
+    #[test]
+    fn png_bytes() {
+        let chart = Chart::read("cell_size=10\nCHART\n.X.\n".as_bytes()).unwrap();
+        let png = PngRenderer
+            .render_to_bytes(&chart, RenderOptions::default())
+            .unwrap();
+
+        assert_eq!(b"\x89PNG\r\n\x1a\n", &png[..8]);
+        let image = image::load_from_memory(&png).unwrap().to_rgba();
+        assert_eq!((30, 10), image.dimensions());
+    }
+
+    #[test]
+    fn png_metadata() {
+        let chart =
+            Chart::read("title=Diamond\ncopyright=© Me\ndpi=254\nCHART\n.\n".as_bytes()).unwrap();
+        let png = PngRenderer
+            .render_to_bytes(&chart, RenderOptions::default())
+            .unwrap();
+        let chunks = chunks(&png);
+
+        assert!(chunks.contains(&("tEXt".into(), b"Title\0Diamond".to_vec())));
+        assert!(chunks.contains(&("tEXt".into(), b"Copyright\0\xa9 Me".to_vec())));
+        // 254 dpi is 10000 pixels per meter.
+        assert!(chunks.contains(&("pHYs".into(), vec![0, 0, 0x27, 0x10, 0, 0, 0x27, 0x10, 1])));
+    }
+}
@@ -0,0 +1,218 @@
+use crate::chart::Chart;
+use crate::errors::*;
+
+#[derive(Debug, PartialEq)]
+pub struct Repeat {
+    // The size of the motif. The whole width or height of the chart if it doesn't repeat
+    // that way.
+    pub width: usize,
+    pub height: usize,
+    // Repeats are lined up with the bottom right of the chart, where knitting starts. These
+    // are the columns on the left and the rows on the top that only hold part of a repeat.
+    pub col_offset: usize,
+    pub row_offset: usize,
+    // Charts drawn at a bigger scale have each cell of the motif repeated as a block of
+    // col_scale stitches by row_scale rows. 1 if not.
+    pub col_scale: usize,
+    pub row_scale: usize,
+}
+
+impl Repeat {
+    // A chart of a single repeat at its own scale, with the same header as the original.
+    pub fn motif(&self, chart: &Chart) -> Result<Chart> {
+        let stitches: Vec<Vec<_>> = (self.row_offset..self.row_offset + self.height)
+            .step_by(self.row_scale)
+            .map(|row| {
+                (self.col_offset..self.col_offset + self.width)
+                    .step_by(self.col_scale)
+                    .map(|col| chart.stitch(row, col))
+                    .collect()
+            })
+            .collect();
+
+        let mut header = chart.header().clone();
+        if header.value("rows").is_some() {
+            header.set_value("rows", &(self.height / self.row_scale).to_string());
+        }
+        if header.value("columns").is_some() {
+            header.set_value("columns", &(self.width / self.col_scale).to_string());
+        }
+        Chart::from_stitches(header, stitches)
+    }
+}
+
+// Finds the smallest motif that the chart repeats, across and up. The chart has to hold at
+// least two whole repeats in a direction to count as repeating that way. A chart drawn with
+// every stitch or row doubled (or more) is scaled back down first. Returns None if it
+// doesn't repeat or scale down either way.
+pub fn find_repeat(chart: &Chart) -> Option<Repeat> {
+    let same_cols = |a: usize, b: usize| {
+        (0..chart.rows()).all(|row| chart.stitch(row, a) == chart.stitch(row, b))
+    };
+    let same_rows = |a: usize, b: usize| {
+        (0..chart.columns()).all(|col| chart.stitch(a, col) == chart.stitch(b, col))
+    };
+    let col_scale = scale(chart.columns(), same_cols);
+    let row_scale = scale(chart.rows(), same_rows);
+
+    let width = col_scale
+        * smallest_period(chart.columns() / col_scale, |a, b| {
+            same_cols(a * col_scale, b * col_scale)
+        });
+    let height = row_scale
+        * smallest_period(chart.rows() / row_scale, |a, b| {
+            same_rows(a * row_scale, b * row_scale)
+        });
+
+    if width == chart.columns() && height == chart.rows() && col_scale == 1 && row_scale == 1 {
+        None
+    } else {
+        Some(Repeat {
+            width,
+            height,
+            col_offset: chart.columns() % width,
+            row_offset: chart.rows() % height,
+            col_scale,
+            row_scale,
+        })
+    }
+}
+
+// The biggest factor of len that splits the lines into groups of matching lines, or 1 if
+// there isn't one. Lines that all match are a repeat, not a scale.
+fn scale(len: usize, same: impl Fn(usize, usize) -> bool) -> usize {
+    if (1..len).all(|i| same(0, i)) {
+        return 1;
+    }
+    (2..=len / 2)
+        .rev()
+        .filter(|factor| len.is_multiple_of(*factor))
+        .find(|factor| (0..len).all(|i| same(i - i % factor, i)))
+        .unwrap_or(1)
+}
+
+// The smallest period that repeats at least twice in len, or len if there isn't one.
+// same(a, b) says whether the lines at a and b match.
+fn smallest_period(len: usize, same: impl Fn(usize, usize) -> bool) -> usize {
+    (1..=len / 2)
+        .find(|period| (0..len - period).all(|i| same(i, i + period)))
+        .unwrap_or(len)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::chart::Stitch;
+
+    fn chart(rows: &str) -> Chart {
+        Chart::read(format!("CHART\n{}", rows).as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn whole_repeats() {
+        let c = chart("X..X..\n.X..X.\nX..X..\n.X..X.\n");
+
+        assert_eq!(
+            Some(Repeat {
+                width: 3,
+                height: 2,
+                col_offset: 0,
+                row_offset: 0,
+                col_scale: 1,
+                row_scale: 1,
+            }),
+            find_repeat(&c)
+        );
+    }
+
+    #[test]
+    fn partial_repeats() {
+        // Two whole repeats across, and none up.
+        let c = chart(".X..X.\nXX.XX.\n");
+        let repeat = find_repeat(&c).unwrap();
+
+        assert_eq!(
+            Repeat {
+                width: 3,
+                height: 2,
+                col_offset: 0,
+                row_offset: 0,
+                col_scale: 1,
+                row_scale: 1,
+            },
+            repeat
+        );
+
+        // Part of a third repeat on the left.
+        let c = chart("XX.XX.XX\n");
+        let repeat = find_repeat(&c).unwrap();
+        assert_eq!(3, repeat.width);
+        assert_eq!(2, repeat.col_offset);
+
+        let motif = repeat.motif(&c).unwrap();
+        assert_eq!(1, motif.rows());
+        assert_eq!(3, motif.columns());
+        assert_eq!(Stitch::Knit, motif.stitch(0, 0));
+        assert_eq!(Stitch::Purl, motif.stitch(0, 1));
+    }
+
+    #[test]
+    fn no_repeat() {
+        assert_eq!(None, find_repeat(&chart("X..\n.X.\n")));
+        // One whole motif and part of another is not enough.
+        assert_eq!(None, find_repeat(&chart("X..X.\n")));
+    }
+
+    #[test]
+    fn scaled_up() {
+        // Every row of jules.knit is doubled, which is the only thing it repeats.
+        let jules = include_str!("../jules.knit");
+        let c = Chart::read(jules.as_bytes()).unwrap();
+        let repeat = find_repeat(&c).unwrap();
+        assert_eq!(
+            (55, 10, 1, 2),
+            (
+                repeat.width,
+                repeat.height,
+                repeat.col_scale,
+                repeat.row_scale
+            )
+        );
+
+        let motif = repeat.motif(&c).unwrap();
+        assert_eq!((5, 55), (motif.rows(), motif.columns()));
+        assert_eq!(c.stitch(2, 11), motif.stitch(1, 11));
+
+        // Scaled 2 x 2, and repeated twice across.
+        let c = chart("XX..XX..\nXX..XX..\n..XX..XX\n..XX..XX\n");
+        let repeat = find_repeat(&c).unwrap();
+        assert_eq!(
+            (4, 4, 2, 2),
+            (
+                repeat.width,
+                repeat.height,
+                repeat.col_scale,
+                repeat.row_scale
+            )
+        );
+        let motif = repeat.motif(&c).unwrap();
+        assert_eq!((2, 2), (motif.rows(), motif.columns()));
+        assert_eq!(Stitch::Purl, motif.stitch(0, 0));
+        assert_eq!(Stitch::Knit, motif.stitch(1, 0));
+    }
+
+    #[test]
+    fn motif_keeps_header() {
+        let c = Chart::read("palette=A:white B:navy\ncolumns=4\nCHART\nABAB\nABAB\n".as_bytes())
+            .unwrap();
+        let motif = find_repeat(&c).unwrap().motif(&c).unwrap();
+
+        let mut out = Vec::new();
+        motif.write(&mut out).unwrap();
+        assert_eq!(
+            "palette=A:white B:navy\ncolumns=2\nCHART\nAB\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+}
@@ -0,0 +1,143 @@
+use crate::attributes::Mode;
+use crate::chart::Chart;
+
+#[derive(Debug, PartialEq)]
+pub struct RowCount {
+    pub row: usize,
+    // Stitches taken off the left needle.
+    pub consumed: usize,
+    // Stitches left on the right needle.
+    pub produced: usize,
+}
+
+// A row that doesn't use up exactly the stitches the row before it left on the needle.
+#[derive(Debug, PartialEq)]
+pub struct CountMismatch {
+    pub row: usize,
+    pub expected: usize,
+    pub found: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct StitchCounts {
+    // In the order they are worked, starting from the bottom of the chart.
+    pub rows: Vec<RowCount>,
+    pub mismatches: Vec<CountMismatch>,
+    pub cast_on: usize,
+    pub bind_off: usize,
+}
+
+// Tracks the live stitch count through the chart, row by row. Each double knitting cell is
+// worked on both faces, so it counts as two stitches.
+pub fn count_stitches(chart: &Chart) -> StitchCounts {
+    let per_cell = if chart.mode() == Mode::DoubleKnitting {
+        2
+    } else {
+        1
+    };
+    let rows: Vec<RowCount> = (0..chart.rows())
+        .rev()
+        .map(|row| {
+            let stitches = (0..chart.columns()).map(|col| chart.stitch(row, col));
+            RowCount {
+                row,
+                consumed: per_cell * stitches.clone().map(|s| s.consumes()).sum::<usize>(),
+                produced: per_cell * stitches.map(|s| s.produces()).sum::<usize>(),
+            }
+        })
+        .collect();
+
+    let mismatches = rows
+        .windows(2)
+        .filter(|w| w[0].produced != w[1].consumed)
+        .map(|w| CountMismatch {
+            row: w[1].row,
+            expected: w[0].produced,
+            found: w[1].consumed,
+        })
+        .collect();
+
+    StitchCounts {
+        cast_on: rows.first().map_or(0, |r| r.consumed),
+        bind_off: rows.last().map_or(0, |r| r.produced),
+        rows,
+        mismatches,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn straight_chart() {
+        let chart = Chart::read("CHART\n.X.\n.X.\n".as_bytes()).unwrap();
+        let counts = count_stitches(&chart);
+
+        assert_eq!(3, counts.cast_on);
+        assert_eq!(3, counts.bind_off);
+        assert!(counts.mismatches.is_empty());
+    }
+
+    #[test]
+    fn double_knitting() {
+        let chart = Chart::read(
+            "mode=double_knitting\npalette=A:black B:white\nCHART\nAB\n/B\n".as_bytes(),
+        )
+        .unwrap();
+        let counts = count_stitches(&chart);
+
+        assert_eq!(6, counts.cast_on);
+        assert_eq!(4, counts.bind_off);
+        assert!(counts.mismatches.is_empty());
+    }
+
+    #[test]
+    fn decreases_with_no_stitch_cells() {
+        // Worked from the bottom: 5 stitches, decreased to 3, then knit.
+        let chart = Chart::read("empty=#\nCHART\n#...#\n#/.\\#\n.....\n".as_bytes()).unwrap();
+        let counts = count_stitches(&chart);
+
+        assert_eq!(5, counts.cast_on);
+        assert_eq!(3, counts.bind_off);
+        assert_eq!(
+            vec![
+                RowCount {
+                    row: 2,
+                    consumed: 5,
+                    produced: 5,
+                },
+                RowCount {
+                    row: 1,
+                    consumed: 5,
+                    produced: 3,
+                },
+                RowCount {
+                    row: 0,
+                    consumed: 3,
+                    produced: 3,
+                },
+            ],
+            counts.rows
+        );
+        assert!(counts.mismatches.is_empty());
+    }
+
+    #[test]
+    fn mismatched_rows() {
+        // The yarn over should have been paired with a decrease, or a no-stitch cell
+        // added to the rows around it.
+        let chart = Chart::read("CHART\n...\n.O.\n...\n".as_bytes()).unwrap();
+        let counts = count_stitches(&chart);
+
+        assert_eq!(
+            vec![CountMismatch {
+                row: 1,
+                expected: 3,
+                found: 2,
+            }],
+            counts.mismatches
+        );
+        assert_eq!(3, counts.bind_off);
+    }
+}
@@ -0,0 +1,188 @@
+use crate::chart::{Chart, Stitch};
+use crate::errors::*;
+
+// A cell that doesn't match the cell it is reflected onto.
+#[derive(Debug, PartialEq)]
+pub struct SymmetryBreak {
+    // (row, col) of the cell, and of its reflection. Each pair is only listed once, with
+    // the cell on the left, or on the top. A leaning stitch in the center column is its own
+    // reflection.
+    pub cell: (usize, usize),
+    pub mirror: (usize, usize),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Symmetry {
+    // Reflecting left to right across the center column.
+    pub left_right: Vec<SymmetryBreak>,
+    // Reflecting top to bottom across the center row.
+    pub top_bottom: Vec<SymmetryBreak>,
+}
+
+impl Symmetry {
+    pub fn is_left_right(&self) -> bool {
+        self.left_right.is_empty()
+    }
+
+    pub fn is_top_bottom(&self) -> bool {
+        self.top_bottom.is_empty()
+    }
+}
+
+// Leaning stitches have to lean the other way in their reflection to be symmetric left to
+// right, so the center column of an odd width chart can't have any. Top to bottom, the
+// stitches have to be the same.
+pub fn find_symmetry(chart: &Chart) -> Symmetry {
+    let rows = chart.rows();
+    let cols = chart.columns();
+
+    let mut left_right = Vec::new();
+    for row in 0..rows {
+        for col in 0..cols.div_ceil(2) {
+            let mirror = cols - 1 - col;
+            if chart.stitch(row, col).mirrored() != chart.stitch(row, mirror) {
+                left_right.push(SymmetryBreak {
+                    cell: (row, col),
+                    mirror: (row, mirror),
+                });
+            }
+        }
+    }
+
+    let mut top_bottom = Vec::new();
+    for row in 0..rows / 2 {
+        let mirror = rows - 1 - row;
+        for col in 0..cols {
+            if chart.stitch(row, col) != chart.stitch(mirror, col) {
+                top_bottom.push(SymmetryBreak {
+                    cell: (row, col),
+                    mirror: (mirror, col),
+                });
+            }
+        }
+    }
+
+    Symmetry {
+        left_right,
+        top_bottom,
+    }
+}
+
+// A copy of the chart with the right half replaced by the reflection of the left half. With
+// an odd number of columns, the center column is kept, so it can't have leaning stitches.
+pub fn complete_from_left(chart: &Chart) -> Result<Chart> {
+    let cols = chart.columns();
+    if cols % 2 == 1 {
+        let center = cols / 2;
+        if let Some(row) = (0..chart.rows()).rev().find(|row| {
+            let stitch = chart.stitch(*row, center);
+            stitch.mirrored() != stitch
+        }) {
+            return Err(ErrorKind::LeaningCenterStitch(chart.row_name(row)).into());
+        }
+    }
+    let stitches = (0..chart.rows())
+        .map(|row| {
+            (0..cols)
+                .map(|col| {
+                    let mirror = cols - 1 - col;
+                    if col <= mirror {
+                        chart.stitch(row, col)
+                    } else {
+                        chart.stitch(row, mirror).mirrored()
+                    }
+                })
+                .collect::<Vec<Stitch>>()
+        })
+        .collect();
+    Chart::from_stitches(chart.header().clone(), stitches)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn chart(rows: &str) -> Chart {
+        Chart::read(format!("CHART\n{}", rows).as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn symmetric() {
+        let c = chart("..X..\n./.\\.\n.X.X.\n");
+        let symmetry = find_symmetry(&c);
+
+        assert!(symmetry.is_left_right());
+        assert!(!symmetry.is_top_bottom());
+        assert_eq!(
+            vec![
+                SymmetryBreak {
+                    cell: (0, 1),
+                    mirror: (2, 1),
+                },
+                SymmetryBreak {
+                    cell: (0, 2),
+                    mirror: (2, 2),
+                },
+                SymmetryBreak {
+                    cell: (0, 3),
+                    mirror: (2, 3),
+                },
+            ],
+            symmetry.top_bottom
+        );
+    }
+
+    #[test]
+    fn asymmetric() {
+        let c = chart("X..\n./.\n");
+        let symmetry = find_symmetry(&c);
+
+        assert_eq!(
+            vec![
+                SymmetryBreak {
+                    cell: (0, 0),
+                    mirror: (0, 2),
+                },
+                SymmetryBreak {
+                    cell: (1, 1),
+                    mirror: (1, 1),
+                },
+            ],
+            symmetry.left_right
+        );
+        assert_eq!(2, symmetry.top_bottom.len());
+    }
+
+    #[test]
+    fn leaning_center() {
+        let c = chart(
+            ".(.
+.X.
+",
+        );
+        assert_eq!(
+            vec![SymmetryBreak {
+                cell: (0, 1),
+                mirror: (0, 1),
+            }],
+            find_symmetry(&c).left_right
+        );
+        assert!(complete_from_left(&c).is_err());
+        assert!(complete_from_left(&chart(
+            ".X..
+"
+        ))
+        .is_ok());
+    }
+
+    #[test]
+    fn complete() {
+        let c = chart("X/.XX.\n.(.X..\n");
+        let completed = complete_from_left(&c).unwrap();
+
+        let mut out = Vec::new();
+        completed.write(&mut out).unwrap();
+        assert_eq!("CHART\nX/..\\X\n.(..).\n", String::from_utf8(out).unwrap());
+        assert!(find_symmetry(&completed).is_left_right());
+    }
+}
@@ -2,14 +2,15 @@ extern crate proc_macro;
 
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{parse_macro_input, Data, DeriveInput, Field, Ident, Lit, Meta, NestedMeta};
+use syn::{parse_macro_input, Data, DeriveInput, Field, Ident, Lit, Meta, NestedMeta, Type};
 
 use proc_macro2::TokenStream as TokenStream2;
 
 /*
  #[derive(StringStruct)] will derive two things for the tagged struct:
  - the Default trait
- - a method, set_value(&mut self, name: &str, value: &str) -> Result<()>.
+ - a method, set_value(&mut self, name: &str, value: &str) -> Result<(), String>, which
+   returns the parse error if the value can't be parsed. Unknown names are ignored.
 
  Each field may be tagged with the 'ssfield' attribute which may contain
  the following sub-attributes:
@@ -36,6 +37,7 @@ use proc_macro2::TokenStream as TokenStream2;
 #[derive(Debug)]
 struct FieldMeta<'a> {
     field_name: &'a Ident,
+    field_type: &'a Type,
     default_string: Option<String>,
     parse_func: Option<Ident>,
 }
@@ -53,6 +55,7 @@ impl<'a> FieldMeta<'a> {
 
         let mut attr_meta = FieldMeta {
             field_name: field.ident.as_ref().unwrap(),
+            field_type: &field.ty,
             default_string: None,
             parse_func: None,
         };
@@ -128,25 +131,32 @@ fn make_set_value_func(
     field_meta: &Vec<FieldMeta>,
 ) -> TokenStream2 {
     let field_names = field_meta.iter().map(|fm| fm.field_name);
+    let all_field_names = field_names.clone();
     let parse_exprs = field_meta
         .iter()
         .map(|am| {
             if let Some(parse_func) = &am.parse_func {
-                quote! { #parse_func(v).unwrap() }
+                quote! { #parse_func(v).map_err(|e| e.to_string())? }
             } else {
-                quote! { v.parse().unwrap() }
+                let field_type = am.field_type;
+                quote! { v.parse::<#field_type>().map_err(|e| e.to_string())? }
             }
         });
 
     let q = quote! {
     impl #struct_name {
-        fn set_value(&mut self, n: &str, v: &str) {
+        fn set_value(&mut self, n: &str, v: &str) -> std::result::Result<(), String> {
         #(
             if std::stringify!(#field_names) == n {
                 self.#field_names = #parse_exprs;
-                return;
+                return Ok(());
             }
         )*
+        Ok(())
+        }
+
+        fn is_value_name(n: &str) -> bool {
+            [#(std::stringify!(#all_field_names)),*].contains(&n)
         }
     }
     };
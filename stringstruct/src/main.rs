@@ -23,8 +23,8 @@ fn parse_char_name(s: &str) -> std::result::Result<char, String> {
 fn main() {
     let mut attrs = Attributes::default();
 
-    attrs.set_value("rows", "18");
-    attrs.set_value("knit_char", "SPACE");
+    attrs.set_value("rows", "18").unwrap();
+    attrs.set_value("knit_char", "SPACE").unwrap();
 
     eprintln!("The attributes: {:?}", attrs);
 }
@@ -40,21 +40,32 @@ fn main() {
     assert_eq!("XXX", ss.emptydefault);
 
     // Then make sure we can set values
-    ss.set_value("b", "true");
+    ss.set_value("b", "true").unwrap();
     assert_eq!(true,ss.b);
 
-    ss.set_value("ew8", "63");
+    ss.set_value("ew8", "63").unwrap();
     assert_eq!(63, ss.ew8);
 
-    ss.set_value("ew32", "123456");
+    ss.set_value("ew32", "123456").unwrap();
     assert_eq!(123456, ss.ew32);
 
-    ss.set_value("s", "modified");
+    ss.set_value("s", "modified").unwrap();
     assert_eq!("modified", ss.s);
 
-    ss.set_value("defsetter", "AAA");
+    ss.set_value("defsetter", "AAA").unwrap();
     assert_eq!("AAAXXX", ss.defsetter);
 
-    ss.set_value("justsetter", "BBB");
+    ss.set_value("justsetter", "BBB").unwrap();
     assert_eq!("BBBXXX", ss.justsetter);
+
+    // A value that doesn't parse is an error, and leaves the field alone.
+    assert!(ss.set_value("ew8", "256").is_err());
+    assert!(ss.set_value("b", "maybe").is_err());
+    assert_eq!(63, ss.ew8);
+    assert_eq!(true, ss.b);
+
+    // Names that aren't fields are ignored.
+    assert!(ss.set_value("nothere", "1").is_ok());
+    assert!(SSTest::is_value_name("ew8"));
+    assert!(!SSTest::is_value_name("nothere"));
 }